@@ -1,7 +1,7 @@
 use std::sync::{Arc, Mutex};
 use tidal_rs::{client::TidalApi, model::AudioQuality};
 
-use crate::{download::DownloadManager, configuration::Configuration, gui::model::GuiInput, database::Database, player::Player, cache::CacheManager};
+use crate::{download::DownloadManager, configuration::Configuration, gui::model::GuiInput, database::Database, player::Player, cache::CacheManager, library::{LibraryAudit, LibraryScanner}, search_index::SearchIndex, undo::UndoStack, watcher::LibraryWatcher};
 
 pub struct UserSettings {
     pub volume: i32,
@@ -27,6 +27,29 @@ impl App {
 
         result.user_settings.volume = result.app.player.get_volume();
 
+        //files that had to be recovered at startup, shown to the user until dismissed
+        if let Some(message) = result.app.configuration.lock().unwrap().recovery() {
+            result.gui_settings.recovery_messages.push(message);
+        }
+
+        if let Some(message) = result.app.database().raw().recovery() {
+            result.gui_settings.recovery_messages.push(message);
+        }
+
+        let library_folders = {
+            result.app.configuration.lock().unwrap().get_library_folders()
+        };
+
+        if let Err(e) = result.app.library_watcher.start(result.app.clone(), library_folders) {
+            println!("Failed to start library watcher : {}", e);
+        }
+
+        //downloads that were still queued when localfy was closed
+        let app = result.app.clone();
+        tokio::spawn(async move {
+            app.download_manager.restore_queue(app.clone()).await;
+        });
+
         result
     }
 }
@@ -37,6 +60,11 @@ pub struct AppImpl {
     pub configuration: Arc<Mutex<Configuration>>,
     pub database:Mutex<Database>,
     pub cache_manager:Arc<tokio::sync::Mutex<CacheManager>>,
+    pub library_scanner: LibraryScanner,
+    pub library_audit: LibraryAudit,
+    pub library_watcher: LibraryWatcher,
+    pub search_index: Mutex<SearchIndex>,
+    pub undo_stack: Mutex<UndoStack>,
     pub player: Player
 }
 
@@ -47,10 +75,15 @@ impl AppImpl {
         let app = Self {
             tidal_client: tidal_client,
             player: Player::new(),
-            download_manager: DownloadManager::new(configuration.max_concurrency()),
+            download_manager: DownloadManager::new(configuration.max_concurrency(), configuration.download_retries()),
+            database: Mutex::new(Database::new(configuration.compress_backups, configuration.compress_database)),
             configuration: Arc::new(Mutex::new(configuration)),
-            database: Mutex::new(Database::new()),
-            cache_manager: Arc::new(tokio::sync::Mutex::new(CacheManager::new()))
+            cache_manager: Arc::new(tokio::sync::Mutex::new(CacheManager::new())),
+            library_scanner: LibraryScanner::new(),
+            library_audit: LibraryAudit::new(),
+            library_watcher: LibraryWatcher::new(),
+            search_index: Mutex::new(SearchIndex::new()),
+            undo_stack: Mutex::new(UndoStack::default())
         };
 
 
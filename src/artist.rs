@@ -0,0 +1,275 @@
+use std::{collections::hash_map::DefaultHasher, hash::{Hash, Hasher}, sync::Arc};
+
+use egui::{vec2, CollapsingHeader, FontFamily, FontId, Image, ImageSource, Label, RichText, Rounding};
+use rand::seq::SliceRandom;
+use tidal_rs::model::Album;
+
+use crate::{app::{App, AppImpl}, cache::CacheManager, constants::{BACKGROUND_COLOR, SELECTION_COLOR, TEXT_COLOR}, gui::{helper::simplify, model::{Pages, UserLocation}, page::RenderablePage, song::SongWidget}, renderer::Drawable, song::Song};
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+pub struct LocalArtist {
+    pub id:String,
+    pub name:String,
+    pub tidal_id:Option<usize>,
+    pub picture:Option<String> //id of the picture on tidal
+}
+
+impl LocalArtist {
+    pub fn id_for_tidal_artist(tidal_id:usize) -> String {
+        format!("tidal:{}", tidal_id)
+    }
+
+    //artists of local files only have a name, the same name is the same artist
+    //the ids are stored, they stay lowercase only even though simplify() now removes the accents too
+    pub fn id_for_name(name:&str) -> String {
+        format!("local:{}", name.trim().to_lowercase())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReleaseType {
+    Album,
+    Ep,
+    Single
+}
+
+impl ReleaseType {
+    //same limits as tidal, applied to the tracks tidal lists for the album when they are known, else to the tracks in the library
+    fn from_track_count(count:usize) -> Self {
+        match count {
+            0..=3 => ReleaseType::Single,
+            4..=6 => ReleaseType::Ep,
+            _ => ReleaseType::Album
+        }
+    }
+}
+
+impl ToString for ReleaseType {
+    fn to_string(&self) -> String {
+        match self {
+            ReleaseType::Album => "Albums".to_string(),
+            ReleaseType::Ep => "EPs".to_string(),
+            ReleaseType::Single => "Singles".to_string()
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Release {
+    pub title:String,
+    pub release_type:ReleaseType,
+    pub album:Option<Album>, //set for songs downloaded from tidal
+    pub downloaded:bool, //the whole album was downloaded, it has a page
+    pub songs:Vec<Song>
+}
+
+//groups the songs of an artist by album
+pub fn discography(songs:Vec<Song>) -> Vec<Release> {
+    let mut releases:Vec<Release> = vec![];
+
+    for song in songs {
+        let title = simplify(&song.album);
+
+        match releases.iter_mut().find(|release| simplify(&release.title) == title) {
+            Some(release) => release.songs.push(song),
+            None => releases.push(Release {
+                title: song.album.clone(),
+                release_type: ReleaseType::Single,
+                album: song.tidal_track.as_ref().and_then(|track| track.album.clone()),
+                downloaded: false,
+                songs: vec![song]
+            })
+        }
+    }
+
+    for release in releases.iter_mut() {
+        release.release_type = ReleaseType::from_track_count(release.songs.len());
+    }
+
+    releases
+}
+
+//what the page shows, read again only when the songs change
+struct ArtistPage {
+    revision:Option<i64>,
+    songs:Vec<Song>,
+    releases:Vec<Release>
+}
+
+impl ArtistPage {
+    fn load(app:&Arc<AppImpl>, artist:&LocalArtist, revision:Option<i64>) -> Self {
+        let database = app.database();
+        let songs = database.artists().get_artist_songs(artist);
+        let mut releases = discography(songs.clone());
+
+        let albums = database.albums();
+        for release in releases.iter_mut() {
+            if let Some(album) = &release.album {
+                release.downloaded = !albums.get_album_song_ids(album).is_empty();
+
+                if let Some(track_count) = albums.get_track_count(album) {
+                    release.release_type = ReleaseType::from_track_count(track_count);
+                }
+            }
+        }
+
+        ArtistPage {
+            revision,
+            songs,
+            releases
+        }
+    }
+}
+
+pub fn play_songs(app:&Arc<AppImpl>, mut songs:Vec<Song>, shuffle:bool) {
+    if shuffle {
+        songs.shuffle(&mut rand::thread_rng());
+    }
+
+    if let Some(first) = songs.first() {
+        let _ = app.player.set_media(first);
+
+        let mut queue = app.player.queue();
+        queue.set_playlist(&songs);
+        queue.current_index = Some(0);
+    }
+}
+
+impl Drawable for LocalArtist {
+    fn id(&self) -> usize {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish() as usize
+    }
+
+    fn get_title(&self) -> String {
+        self.name.clone()
+    }
+
+    fn get_texture(&self) -> ImageSource {
+        if let Some(picture) = &self.picture {
+            let url = format!("https://resources.tidal.com/images/{}/320x320.jpg", picture.replace("-", "/"));
+            return ImageSource::Uri(url.into());
+        }
+
+        CacheManager::get_default_cover()
+    }
+}
+
+impl RenderablePage for LocalArtist {
+    fn get_page_title(&self) -> String {
+        self.name.clone()
+    }
+
+    fn render(&self, application:&mut App, ui:&mut egui::Ui, max_rect:egui::Rect) {
+        let revision = {
+            application.app.database().songs().revision()
+        };
+
+        let cache_id = egui::Id::new(("_artist_page_", &self.id));
+        let page = match ui.memory(|mem| mem.data.get_temp::<Arc<ArtistPage>>(cache_id)) {
+            Some(page) if page.revision == revision => page,
+            _ => {
+                let page = Arc::new(ArtistPage::load(&application.app, self, revision));
+                ui.memory_mut(|mem| mem.data.insert_temp(cache_id, page.clone()));
+                page
+            }
+        };
+
+        let songs = &page.songs;
+        let releases = &page.releases;
+
+        //artists of local files have no picture, the cover of one of their songs is used instead
+        let picture = match (&self.picture, songs.first()) {
+            (None, Some(song)) => song.get_texture(),
+            _ => self.get_texture()
+        };
+
+        let mut list_rect = max_rect.expand2(egui::vec2(0., -50.)).shrink(35.);
+        let font = FontId::new(17., FontFamily::Name("Montserrat".into()));
+
+        let res = ui.scope(|ui| {
+            ui.style_mut().spacing.item_spacing.x += 15.;
+            ui.horizontal(|ui| {
+                ui.add(Image::new(picture).fit_to_exact_size(vec2(60., 60.)).rounding(Rounding::same(30.)));
+                ui.add(Label::new(RichText::new(&self.name).font(font).color(TEXT_COLOR)));
+
+                if ui.button("Play all").clicked() {
+                    play_songs(&application.app, songs.clone(), false);
+                }
+
+                if ui.button("Shuffle").clicked() {
+                    play_songs(&application.app, songs.clone(), true);
+                }
+
+                if ui.button("go back").clicked() {
+                    application.gui_settings.location = UserLocation::Home;
+                    application.gui_settings.page = Pages::Home;
+                }
+            }).response
+        }).inner;
+
+        let padding_rect = res.rect.expand(15.);
+        if list_rect.intersects(padding_rect) {
+            list_rect.min.y = padding_rect.max.y + 15.0;
+        }
+
+        let paint_rect = list_rect.expand(10.0);
+        ui.painter().rect_filled(paint_rect, 10.0, BACKGROUND_COLOR);
+
+        let mut container = ui.child_ui(list_rect, egui::Layout::default());
+
+        egui::ScrollArea::new([false, true]).show(&mut container, |container: &mut egui::Ui| {
+            for release_type in [ReleaseType::Album, ReleaseType::Ep, ReleaseType::Single] {
+                let releases = releases.iter().filter(|release| release.release_type == release_type).collect::<Vec<&Release>>();
+
+                if releases.is_empty() {
+                    continue;
+                }
+
+                container.label(RichText::new(release_type.to_string()).strong());
+
+                for release in releases {
+                    CollapsingHeader::new(format!("{} ({})", release.title, release.songs.len()))
+                        .id_source(("_artist_release_", &self.id, &release.title))
+                        .show(container, |container| {
+                            //only albums downloaded as a whole have a page
+                            let downloaded_album = release.album.clone().filter(|_| release.downloaded);
+
+                            if let Some(album) = downloaded_album {
+                                if container.button("Open album").clicked() {
+                                    application.gui_settings.location = UserLocation::Album(album);
+                                    application.gui_settings.page = Pages::Album;
+                                }
+                            }
+
+                            let list = format!("artist:{}:{}", self.id, release.title);
+
+                            release.songs.iter().enumerate().for_each(|(index, song)| {
+                                let widget = if application.gui_settings.selection.is_selected(&list, song) {
+                                    SongWidget::new(song.clone()).background_color(SELECTION_COLOR)
+                                } else {
+                                    SongWidget::new(song.clone())
+                                };
+
+                                let response = container.add(widget);
+
+                                if response.clicked() {
+                                    let modifiers = container.input(|input| input.modifiers);
+                                    if !application.gui_settings.selection.handle_click(&list, &release.songs, index, modifiers) {
+                                        song.on_clicked(application.app.clone(), UserLocation::Artist(self.clone()));
+                                    }
+                                }
+
+                                song.context_menu(response, ui, application);
+                            });
+                        });
+                }
+            }
+        });
+    }
+
+    fn get_page_icon(&self) -> ImageSource {
+        self.get_texture()
+    }
+}
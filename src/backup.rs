@@ -0,0 +1,97 @@
+use std::{fs::File, io::{BufRead, BufReader}, path::{Path, PathBuf}};
+
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::migration;
+
+//number of timestamped backups kept next to the database, the oldest ones are deleted
+pub const MAX_BACKUPS:usize = 5;
+//a backup is made at startup when the last one is older than this
+pub const BACKUP_INTERVAL:u64 = 24 * 60 * 60;
+
+const BACKUP_PREFIX:&str = "backup-";
+
+#[derive(Clone)]
+pub struct BackupEntry {
+    pub path:PathBuf,
+    pub created_at:u64
+}
+
+//the file is written next to its destination, synced then renamed over it so a crash never leaves a half written file
+pub fn write_atomically<F>(path:&Path, write:F) -> std::io::Result<()>
+    where F: FnOnce(&Path) -> std::io::Result<()>
+{
+    let temporary_path = migration::backup_path(path, "tmp");
+    if temporary_path.exists() {
+        std::fs::remove_file(&temporary_path)?;
+    }
+
+    if let Err(e) = write(&temporary_path).and_then(|_| File::open(&temporary_path)?.sync_all()) {
+        let _ = std::fs::remove_file(&temporary_path);
+        return Err(e);
+    }
+
+    std::fs::rename(&temporary_path, path)
+}
+
+//zlib streams start with 0x78, json and sqlite files never do
+pub fn is_compressed(buffer:&[u8]) -> bool {
+    buffer.first() == Some(&0x78)
+}
+
+pub fn is_compressed_file(path:&Path) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(is_compressed(reader.fill_buf()?))
+}
+
+pub fn compress_file(from:&Path, to:&Path) -> std::io::Result<()> {
+    let mut encoder = ZlibEncoder::new(File::create(to)?, Compression::best());
+    std::io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?;
+
+    Ok(())
+}
+
+pub fn decompress_file(from:&Path, to:&Path) -> std::io::Result<()> {
+    let mut decoder = ZlibDecoder::new(File::open(from)?);
+    std::io::copy(&mut decoder, &mut File::create(to)?)?;
+
+    Ok(())
+}
+
+pub fn get_backup_path(path:&Path, created_at:u64) -> PathBuf {
+    migration::backup_path(path, &format!("{}{}", BACKUP_PREFIX, created_at))
+}
+
+//newest first
+pub fn list_backups(path:&Path) -> Vec<BackupEntry> {
+    let file_name = path.file_name().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    let prefix = format!("{}.{}", file_name, BACKUP_PREFIX);
+    let folder = path.parent().filter(|x| !x.as_os_str().is_empty()).unwrap_or(Path::new("."));
+
+    let mut backups = std::fs::read_dir(folder)
+        .map(|entries| entries.filter_map(|entry| entry.ok()).collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let created_at = name.strip_prefix(&prefix)?.parse::<u64>().ok()?;
+
+            Some(BackupEntry {
+                path: entry.path(),
+                created_at
+            })
+        })
+        .collect::<Vec<BackupEntry>>();
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+    backups
+}
+
+pub fn rotate_backups(path:&Path) -> std::io::Result<()> {
+    for backup in list_backups(path).into_iter().skip(MAX_BACKUPS) {
+        std::fs::remove_file(backup.path)?;
+    }
+
+    Ok(())
+}
@@ -1,7 +1,64 @@
-use std::{collections::hash_map::DefaultHasher, fs::File, hash::{Hash, Hasher}, path::PathBuf, sync::Arc};
+use std::{collections::hash_map::DefaultHasher, fs::File, hash::{Hash, Hasher}, path::PathBuf, sync::{Arc, Mutex, OnceLock}};
 use std::io::Write;
 use egui::{ahash::{HashMap, HashMapExt}, load::Bytes, ImageSource};
-use image::EncodableLayout;
+
+const MAX_LOADED_COVERS:usize = 256;
+
+//covers are asked for on every frame, each file is only read once while it is in use
+static LOADED_COVERS: OnceLock<Mutex<LruCache<u64, Option<Arc<[u8]>>>>> = OnceLock::new();
+
+fn loaded_covers() -> std::sync::MutexGuard<'static, LruCache<u64, Option<Arc<[u8]>>>> {
+    LOADED_COVERS.get_or_init(|| Mutex::new(LruCache::new(MAX_LOADED_COVERS))).lock().unwrap()
+}
+
+//keeps the entries used last, the one used the longest ago is dropped when it is full
+pub struct LruCache<K, V> {
+    capacity:usize,
+    tick:u64,
+    entries:std::collections::HashMap<K, (V, u64)>
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity:usize) -> Self {
+        LruCache {
+            capacity,
+            tick: 0,
+            entries: std::collections::HashMap::new()
+        }
+    }
+
+    pub fn get(&mut self, key:&K) -> Option<V> {
+        self.tick += 1;
+        let tick = self.tick;
+
+        self.entries.get_mut(key).map(|(value, used_at)| {
+            *used_at = tick;
+            value.clone()
+        })
+    }
+
+    pub fn insert(&mut self, key:K, value:V) {
+        self.tick += 1;
+        self.entries.insert(key, (value, self.tick));
+
+        if self.entries.len() > self.capacity {
+            let oldest = self.entries.iter().min_by_key(|(_, (_, used_at))| *used_at).map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get_or_insert_with(&mut self, key:K, value:impl FnOnce() -> V) -> V {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+
+        let value = value();
+        self.insert(key, value.clone());
+        value
+    }
+}
 
 pub struct CachedObject {
     pub data:Vec<u8>,
@@ -50,17 +107,15 @@ impl CacheManager {
     pub fn source_from_id<'a>(id:String) -> Option<ImageSource<'a>>
     {
         let id = id.parse::<u64>().ok()?;
-        let path = Self::get_path_for_hash(id);
 
-        dbg!(&path);
+        let data = loaded_covers().get_or_insert_with(id, || std::fs::read(Self::get_path_for_hash(id)).ok().map(Arc::from))?;
 
-        if path.exists() {
-            let data = std::fs::read(&path).unwrap();
+        Some(ImageSource::Bytes { uri: format!("bytes://{id}").into(), bytes: Bytes::Shared(data) })
+    }
 
-            Some(ImageSource::Bytes { uri: format!("bytes://{id}").into(), bytes: Bytes::Shared(Arc::from(data.as_bytes())) })
-        } else {
-            None
-        }
+    pub fn data_from_id(id:&str) -> Option<Vec<u8>> {
+        let id = id.parse::<u64>().ok()?;
+        std::fs::read(Self::get_path_for_hash(id)).ok()
     }
 
     pub fn get_default_cover() -> ImageSource<'static> {
@@ -87,15 +142,21 @@ impl CacheManager {
         Self::get_base_path().join(hash.to_string())
     }
 
-    pub fn add(&mut self, key:impl Hash, data:Vec<u8>) -> std::io::Result<()> {
+    pub fn hash_key(key:impl Hash) -> u64 {
         let mut hasher = DefaultHasher::new();
         key.hash(&mut hasher);
 
-        let hash = hasher.finish();
-        let object = self.create_cache_file(hash, data);
-        self.items.insert(hash, object?);
+        hasher.finish()
+    }
+
+    pub fn add(&mut self, key:impl Hash, data:Vec<u8>) -> std::io::Result<u64> {
+        let hash = Self::hash_key(key);
+        let object = self.create_cache_file(hash, data)?;
+
+        loaded_covers().insert(hash, Some(Arc::from(object.data.clone())));
+        self.items.insert(hash, object);
 
-        Ok(())
+        Ok(hash)
     }
 
     pub fn create_cache_file(&mut self, key:u64, data:Vec<u8>) -> std::io::Result<CachedObject> {
@@ -108,10 +169,6 @@ impl CacheManager {
     }
 
     pub fn get(&self, key:impl Hash) -> Option<&CachedObject> {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-
-
-        self.items.get(&hasher.finish())
+        self.items.get(&Self::hash_key(key))
     }
 }
\ No newline at end of file
@@ -1,24 +1,44 @@
-use std::path::PathBuf;
+use std::{io::Write, path::{Path, PathBuf}};
 
 
 use tidal_rs::model::AudioQuality;
 
+use crate::{backup, migration, time};
+
 #[derive(serde::Deserialize, serde::Serialize)]
 pub struct Configuration {
+    #[serde(default)]
+    pub version: usize,
     pub refresh_token:Option<String>,
     pub base_download_folder:Option<PathBuf>,
     pub quality: Option<AudioQuality>,
     #[serde(default)]
-    pub max_concurrency: usize
+    pub max_concurrency: usize,
+    #[serde(default)]
+    pub download_retries: usize,
+    #[serde(default)]
+    pub library_folders: Vec<PathBuf>,
+    #[serde(default)]
+    pub compress_backups: bool,
+    #[serde(default)]
+    pub compress_database: bool,
+    #[serde(skip)]
+    recovery: Option<String>
 }
 
 impl Default for Configuration {
     fn default() -> Self {
         Configuration {
+            version: migration::CONFIGURATION_MIGRATIONS.len(),
             refresh_token:None,
             base_download_folder: None,
             quality: None,
-            max_concurrency: 10
+            max_concurrency: 10,
+            download_retries: 3,
+            library_folders: Vec::new(),
+            compress_backups: false,
+            compress_database: false,
+            recovery: None
         }
     }
 }
@@ -40,6 +60,10 @@ impl Configuration {
         self.max_concurrency = max_concurrency;
     }
 
+    pub fn download_retries(&self) -> usize {
+        self.download_retries
+    }
+
     pub fn get_base_download_folder(&self) -> PathBuf {
         if let Some(base_download_folder) = &self.base_download_folder {
             return base_download_folder.to_path_buf();
@@ -61,6 +85,29 @@ impl Configuration {
         PathBuf::from("Downloads")
     }
 
+    pub fn add_library_folder(&mut self, folder:PathBuf) -> () {
+        if !self.library_folders.contains(&folder) {
+            self.library_folders.push(folder);
+        }
+    }
+
+    pub fn remove_library_folder(&mut self, folder:&PathBuf) -> () {
+        self.library_folders.retain(|x| x != folder);
+    }
+
+    //every folder watched for changes, the download folder included
+    pub fn get_library_folders(&self) -> Vec<PathBuf> {
+        let mut folders = vec![self.get_base_download_folder()];
+
+        for folder in &self.library_folders {
+            if !folders.contains(folder) {
+                folders.push(folder.clone());
+            }
+        }
+
+        folders
+    }
+
     fn get_path() -> PathBuf {
         if let Ok(program_data) = std::env::var("PROGRAMDATA") {
             let path_buf = PathBuf::from(program_data)
@@ -77,6 +124,23 @@ impl Configuration {
         PathBuf::from("config.json")
     }
 
+    //set when config.json couldn't be read and the default configuration is used instead
+    pub fn recovery(&self) -> Option<String> {
+        self.recovery.clone()
+    }
+
+    fn load(path:&Path) -> Result<Self, std::io::Error> {
+        let file = std::fs::File::open(path)?;
+        let reader = std::io::BufReader::new(file);
+
+        let mut value: serde_json::Value = serde_json::from_reader(reader)?;
+        migration::migrate_configuration(&mut value, path)?;
+
+        let config: Configuration = serde_json::from_value(value)?;
+
+        Ok(config)
+    }
+
     pub fn new() -> Self {
         let path = Self::get_path();
 
@@ -87,22 +151,47 @@ impl Configuration {
             config.flush();
 
             return config;
-        } else {
-            let file = std::fs::File::open(path).unwrap();
-            let reader = std::io::BufReader::new(file);
+        }
 
-            let config: Configuration = serde_json::from_reader(reader).unwrap();
+        match Configuration::load(&path) {
+            Ok(config) => {
+                config.flush();
+                config
+            },
+            Err(e) => {
+                println!("Failed to read configuration : {}", e);
 
-            return config;
+                //the file is kept so the settings can be recovered by hand
+                let timestamp = time::now();
+                let broken_path = migration::backup_path(&path, &format!("broken-{}", timestamp));
+
+                let message = match std::fs::rename(&path, &broken_path) {
+                    Ok(_) => format!("The settings could not be read ({}). They were moved to {} and the default settings are used.", e, broken_path.display()),
+                    Err(_) => format!("The settings could not be read ({}). The default settings are used.", e)
+                };
+
+                let mut config = Configuration::default();
+                config.recovery = Some(message);
+
+                config.flush();
+
+                config
+            }
         }
     }
+
     pub fn flush(&self) -> () {
         let path = Configuration::get_path();
 
-        let file = std::fs::File::create(path).unwrap();
-        let writer = std::io::BufWriter::new(file);
+        let result = backup::write_atomically(&path, |temporary_path| {
+            let mut writer = std::io::BufWriter::new(std::fs::File::create(temporary_path)?);
+            serde_json::to_writer(&mut writer, self)?;
+            writer.flush()
+        });
 
-        serde_json::to_writer(writer, self).unwrap();
+        if let Err(e) = result {
+            println!("Failed to save configuration : {}", e);
+        }
     }
 }
 
@@ -8,5 +8,6 @@ pub const SECONDARY_ACTION_COLOR:Color32 = Color32::from_rgb(0x9f,0x9f, 0x9f);
 pub const SECONDARY_ACTION_COLOR_HOVER:Color32 = Color32::from_rgb(0xff,0xff, 0xff);
 pub const SECONDARY_HOVER_COLOR:Color32 = Color32::from_rgb(0x1d, 0xb9, 0x54);
 pub const WARNING_COLOR:Color32 = Color32::from_rgb(0xff, 0x7f, 0x00);
+pub const SELECTION_COLOR:Color32 = Color32::from_rgb(0x1d, 0x3b, 0x2a);
 
 pub const CONTROLS_SIZE_PERCENT:f32 = 0.1;
\ No newline at end of file
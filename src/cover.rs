@@ -0,0 +1,209 @@
+use std::{collections::HashSet, io::Cursor, path::PathBuf, sync::{Arc, Mutex, MutexGuard, OnceLock}, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use egui::{load::Bytes, ImageSource};
+use image::{imageops::{self, FilterType}, DynamicImage, ImageOutputFormat, RgbaImage};
+
+use crate::{app::AppImpl, cache::{CacheManager, LruCache}, gui::helper::simplify, playlist::PlaylistDescriptor, song::Song};
+
+const MOSAIC_SIZE: u32 = 300;
+const MOSAIC_TILES: usize = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+const MAX_MOSAIC_ATTEMPTS: usize = 256;
+const MAX_LOADED_IMAGES: usize = 64;
+
+//what the last generation of the mosaic of a playlist gave
+#[derive(Clone)]
+struct MosaicAttempt {
+    cover:Option<String>,
+    revision:Option<i64>,
+    at:Instant
+}
+
+struct Mosaics {
+    generating:HashSet<String>,
+    attempts:LruCache<String, MosaicAttempt>
+}
+
+static MOSAICS: OnceLock<Mutex<Mosaics>> = OnceLock::new();
+
+//the image picked by the user is asked for on every frame, it is read again only when the file was modified
+static LOADED_IMAGES: OnceLock<Mutex<LruCache<PathBuf, (Option<SystemTime>, Option<Arc<[u8]>>)>>> = OnceLock::new();
+
+fn mosaics() -> MutexGuard<'static, Mosaics> {
+    MOSAICS.get_or_init(|| Mutex::new(Mosaics {
+        generating: HashSet::new(),
+        attempts: LruCache::new(MAX_MOSAIC_ATTEMPTS)
+    })).lock().unwrap()
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+enum CoverSource {
+    Tidal(String), //id of the album cover on tidal
+    Cache(String) //id of the cover in the cache, for songs imported from local files
+}
+
+fn cover_source(song:&Song) -> Option<CoverSource> {
+    if let Some(cover) = song.tidal_track.as_ref().and_then(|track| track.album.as_ref().map(|album| album.cover.clone())) {
+        return Some(CoverSource::Tidal(cover));
+    }
+
+    song.cover.clone().map(CoverSource::Cache)
+}
+
+//covers of the first distinct albums of the playlist, songs without a cover are skipped
+fn album_covers(songs:&Vec<Song>) -> Vec<CoverSource> {
+    let mut albums = HashSet::new();
+    let mut covers = vec![];
+
+    for song in songs {
+        if covers.len() == MOSAIC_TILES {
+            break;
+        }
+
+        if let Some(source) = cover_source(song) {
+            if albums.insert((simplify(&song.artist), simplify(&song.album))) {
+                covers.push(source);
+            }
+        }
+    }
+
+    covers
+}
+
+async fn load_cover(source:&CoverSource) -> Option<DynamicImage> {
+    let data = match source {
+        CoverSource::Tidal(cover) => {
+            let url = format!("https://resources.tidal.com/images/{}/320x320.jpg", cover.replace("-", "/"));
+            reqwest::get(url).await.ok()?.bytes().await.ok()?.to_vec()
+        },
+        CoverSource::Cache(id) => CacheManager::data_from_id(id)?
+    };
+
+    image::load_from_memory(&data).ok()
+}
+
+//a 2x2 grid when there are enough albums, the first cover alone otherwise
+fn compose(covers:&Vec<DynamicImage>) -> Option<DynamicImage> {
+    if covers.len() < MOSAIC_TILES {
+        return covers.first().map(|cover| cover.resize_to_fill(MOSAIC_SIZE, MOSAIC_SIZE, FilterType::Triangle));
+    }
+
+    let tile_size = MOSAIC_SIZE / 2;
+    let mut mosaic = RgbaImage::new(MOSAIC_SIZE, MOSAIC_SIZE);
+
+    for (index, cover) in covers.iter().enumerate() {
+        let tile = cover.resize_to_fill(tile_size, tile_size, FilterType::Triangle);
+        let x = (index % 2) as u32 * tile_size;
+        let y = (index / 2) as u32 * tile_size;
+
+        imageops::overlay(&mut mosaic, &tile.to_rgba8(), x as i64, y as i64);
+    }
+
+    Some(DynamicImage::ImageRgba8(mosaic))
+}
+
+async fn generate_mosaic(app:Arc<AppImpl>, playlist:PlaylistDescriptor) -> Option<String> {
+    let songs = {
+        app.database().playlists().unhash_playlist_songs(&playlist)
+    }?.songs;
+
+    let sources = album_covers(&songs);
+
+    //the same albums give the same cache entry, playlists sharing them share the file
+    let key = ("playlist_mosaic", &sources);
+    if app.cache_manager.lock().await.get(&key).is_some() {
+        return Some(CacheManager::hash_key(&key).to_string());
+    }
+
+    let mut covers = vec![];
+    for source in &sources {
+        if let Some(cover) = load_cover(source).await {
+            covers.push(cover);
+        }
+    }
+
+    let mut data = vec![];
+    compose(&covers)?.write_to(&mut Cursor::new(&mut data), ImageOutputFormat::Png).ok()?;
+
+    let id = app.cache_manager.lock().await.add(&key, data).ok()?;
+
+    Some(id.to_string())
+}
+
+//generates the mosaic of the playlist in the background if it doesn't have one yet or if it may be outdated
+pub fn request_mosaic(app:&Arc<AppImpl>, playlist:&PlaylistDescriptor) {
+    //older versions wrote an empty cover while the mosaic was generated
+    let cover = playlist.cover.clone().filter(|cover| !cover.is_empty());
+
+    //the songs of a smart playlist change with the library without its cover being cleared
+    let revision = match playlist.smart {
+        true => app.database().songs().revision(),
+        false => None
+    };
+
+    {
+        let mut mosaics = mosaics();
+        if mosaics.generating.contains(&playlist.id) {
+            return;
+        }
+
+        let outdated = match mosaics.attempts.get(&playlist.id) {
+            Some(attempt) if attempt.cover != cover || attempt.revision != revision => true,
+            Some(attempt) => cover.is_none() && attempt.at.elapsed() >= RETRY_DELAY,
+            None => cover.is_none() || playlist.smart
+        };
+
+        if !outdated {
+            return;
+        }
+
+        mosaics.generating.insert(playlist.id.clone());
+    }
+
+    let app = app.clone();
+    let playlist = playlist.clone();
+
+    tokio::spawn(async move {
+        let generated = generate_mosaic(app.clone(), playlist.clone()).await;
+        if generated != cover {
+            let _ = app.database().playlists().set_cover(&playlist, generated.as_deref());
+        }
+
+        let mut mosaics = mosaics();
+        mosaics.generating.remove(&playlist.id);
+        mosaics.attempts.insert(playlist.id.clone(), MosaicAttempt { cover: generated, revision, at: Instant::now() });
+    });
+}
+
+//the image picked by the user, then the generated mosaic, then the default cover
+pub fn playlist_cover<'a>(playlist:&PlaylistDescriptor) -> ImageSource<'a> {
+    if let Some(image) = &playlist.image {
+        let modified = std::fs::metadata(image).and_then(|metadata| metadata.modified()).ok();
+
+        let data = {
+            let mut loaded_images = LOADED_IMAGES.get_or_init(|| Mutex::new(LruCache::new(MAX_LOADED_IMAGES))).lock().unwrap();
+
+            match loaded_images.get(image) {
+                Some((loaded_at, data)) if loaded_at == modified => data,
+                _ => {
+                    let data = std::fs::read(image).ok().map(Arc::from);
+                    loaded_images.insert(image.clone(), (modified, data.clone()));
+                    data
+                }
+            }
+        };
+
+        //egui keeps the image it decoded for an uri, a modified file needs a new one
+        let version = modified.and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()).map(|age| age.as_millis()).unwrap_or_default();
+
+        if let Some(data) = data {
+            return ImageSource::Bytes { uri: format!("bytes://{}?{}", image.to_string_lossy(), version).into(), bytes: Bytes::Shared(data) };
+        }
+    }
+
+    if let Some(source) = playlist.cover.clone().and_then(CacheManager::source_from_id) {
+        return source;
+    }
+
+    CacheManager::get_default_cover()
+}
@@ -1,23 +1,19 @@
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
     fs::File,
-    sync::{ Arc, Mutex },
+    sync::{ Arc, Mutex, MutexGuard },
     collections::{hash_map::DefaultHasher, HashMap},
     hash::Hasher,
-    io::BufRead, time::Instant,
+    io::BufRead,
 };
 
 use std::hash::Hash;
 
-use tidal_rs::model::{Album, Track};
+use flate2::read::ZlibDecoder;
+use rusqlite::{backup::Progress, params, Connection, DatabaseName, OptionalExtension, Row};
+use tidal_rs::model::{Album, AudioQuality, Track};
 
-use crate::{ app::AppImpl, playlist::{DecodedPlaylist, Playlist, PlaylistDescriptor}, song::Song };
-
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct CachedHashSong {
-    pub hash: u64,
-    pub song: Song,
-}
+use crate::{ artist::LocalArtist, backup::{self, BackupEntry}, history::{PlayEvent, PlayEventKind, SongStats}, migration, playlist::{DecodedPlaylist, Playlist, PlaylistDescriptor, PlaylistFolder}, smart_playlist::SmartRules, song::Song, time, trash::TrashEntry };
 
 #[derive(Clone, Debug, Hash, serde::Serialize, serde::Deserialize)]
 pub struct AlbumHashed {
@@ -37,27 +33,12 @@ impl PartialEq for AlbumHashed {
     }
 }
 
-impl PartialEq for CachedHashSong {
-    fn eq(&self, other: &Self) -> bool {
-        self.hash == other.hash
-    }
-}
-
-impl From<Song> for CachedHashSong {
-    fn from(value: Song) -> Self {
-        let mut hasher = DefaultHasher::new();
-        value.hash(&mut hasher);
-
-        CachedHashSong {
-            hash: hasher.finish(),
-            song: value,
-        }
-    }
-}
-impl From<&Song> for CachedHashSong {
-    fn from(value: &Song) -> Self {
-        CachedHashSong::from(value.clone())
-    }
+#[derive(Clone, Debug, Hash, serde::Serialize, serde::Deserialize)]
+pub struct HashedPlaylist {
+    pub id:String,
+    pub name:String,
+    pub image:Option<PathBuf>,
+    pub songs:Vec<u64>
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -77,12 +58,13 @@ impl Default for TrackHashMap {
     }
 }
 
+//format of the old json localfy.db, only used to import it into sqlite
 #[derive(Clone, Debug, Hash, serde::Serialize, serde::Deserialize)]
 pub struct DatabaseDataContainer {
     #[serde(default)]
     pub tracks: TrackHashMap,
     #[serde(default)]
-    pub playlists: Vec<Playlist>,
+    pub playlists: Vec<HashedPlaylist>,
     #[serde(default)]
     pub albums: Vec<AlbumHashed>,
 }
@@ -109,58 +91,21 @@ impl Default for DatabaseDataContainer {
     }
 }
 
-pub type DatabaseData = std::sync::Arc<std::sync::Mutex<DatabaseDataContainer>>;
-
 #[derive(Clone)]
-pub struct Database 
+pub struct Database
 {
     pub inner: Arc<DatabaseImpl>,
-    last_save_hash: u64,
-    last_save_time: std::time::Instant
-}
-
-impl Drop for Database {
-    fn drop(&mut self) {
-        self.flush().unwrap();
-    }
 }
 
 impl Database {
-    pub fn new() -> Self {
-        let database = DatabaseImpl::new();
-        let hash = database.hash();
-
+    pub fn new(compress_backups:bool, compress_database:bool) -> Self {
         Database {
-            inner: Arc::new(database),
-            last_save_hash: hash,
-            last_save_time: Instant::now()
+            inner: Arc::new(DatabaseImpl::new(compress_backups, compress_database)),
         }
     }
 
     pub fn raw(&self) -> Arc<DatabaseImpl> {
         self.inner.clone()
-    } 
-
-    pub fn last_save_hash(&self) -> u64 {
-        self.last_save_hash
-    }
-
-    pub fn last_save_time(&self) -> Instant {
-        self.last_save_time
-    }
-
-    pub fn flush(&mut self) -> Result<(), std::io::Error> {
-        let str = {
-            let data = self.inner.data.lock().unwrap();
-            serde_json::to_string(&data.clone()).unwrap()
-        };
-
-        // let mut e = ZlibEncoder::new(Vec::new(), Compression::best());
-        // e.write_all(str.as_bytes())?;
-        std::fs::write(DatabaseImpl::get_database_path(), str)?;
-
-        self.last_save_hash = self.inner.hash();
-        Ok(())
     }
 
     pub fn songs(&self) -> SongController {
@@ -180,11 +125,38 @@ impl Database {
             database: self.inner.clone(),
         }
     }
+
+    pub fn trash(&self) -> TrashController {
+        TrashController {
+            database: self.inner.clone(),
+        }
+    }
+
+    pub fn history(&self) -> HistoryController {
+        HistoryController {
+            database: self.inner.clone(),
+        }
+    }
+
+    pub fn artists(&self) -> ArtistController {
+        ArtistController {
+            database: self.inner.clone(),
+        }
+    }
+
+    pub fn downloads(&self) -> DownloadController {
+        DownloadController {
+            database: self.inner.clone(),
+        }
+    }
 }
 
-#[derive(Clone)]
 pub struct DatabaseImpl {
-    pub data: Arc<Mutex<DatabaseDataContainer>>,
+    connection: Mutex<Connection>,
+    path: PathBuf,
+    //set when the library is stored compressed, sqlite works on this decompressed copy
+    working_path: Option<PathBuf>,
+    recovery: Option<String>,
 }
 
 pub struct SongController {
@@ -201,120 +173,706 @@ pub struct AlbumController {
     database: Arc<DatabaseImpl>,
 }
 
+pub struct TrashController {
+    database: Arc<DatabaseImpl>,
+}
+
+pub struct HistoryController {
+    database: Arc<DatabaseImpl>,
+}
+
+pub struct ArtistController {
+    database: Arc<DatabaseImpl>,
+}
+
+pub struct DownloadController {
+    database: Arc<DatabaseImpl>,
+}
+
+//a download waiting in the queue, its stream url is asked again when it is restored because they expire
+pub struct QueuedDownload {
+    pub track:Track,
+    pub quality:AudioQuality,
+    pub playlist:Option<Playlist>,
+    pub album:Option<Album>,
+    pub paused:bool
+}
+
+fn song_from_row(row:&Row) -> rusqlite::Result<Song> {
+    let path:String = row.get("path")?;
+    let tidal_track:Option<String> = row.get("tidal_track")?;
+
+    Ok(Song {
+        id: row.get("id")?,
+        path: PathBuf::from(path),
+        title: row.get("title")?,
+        artist: row.get("artist")?,
+        album: row.get("album")?,
+        track_number: row.get("track_number")?,
+        duration: row.get::<_, Option<i64>>("duration")?.map(|duration| duration as u64),
+        cover: row.get("cover")?,
+        added_at: row.get::<_, Option<i64>>("added_at")?.map(|added_at| added_at as u64),
+        lossless: row.get("lossless")?,
+        tidal_track: tidal_track.and_then(|json| serde_json::from_str::<Track>(&json).ok())
+    })
+}
+
+//returns the id of the artist of a song, creating it if needed
+pub(crate) fn upsert_artist(connection:&Connection, name:&str, tidal_track:Option<&Track>) -> rusqlite::Result<String> {
+    let local_id = LocalArtist::id_for_name(name);
+
+    if let Some(artist) = tidal_track.map(|track| track.get_artist()) {
+        let id = LocalArtist::id_for_tidal_artist(artist.id);
+
+        connection.execute(
+            "INSERT INTO artists (id, name, tidal_id, picture) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(id) DO UPDATE SET name = excluded.name, picture = COALESCE(excluded.picture, artists.picture)",
+            params![id, artist.name, artist.id as i64, artist.picture]
+        )?;
+
+        //local songs of the same artist imported before are merged into the tidal one
+        connection.execute("UPDATE songs SET artist_id = ?1 WHERE artist_id = ?2", params![id, local_id])?;
+        connection.execute("DELETE FROM artists WHERE id = ?1", params![local_id])?;
+
+        return Ok(id);
+    }
+
+    let existing = connection.query_row(
+        "SELECT id FROM artists WHERE name = ?1 COLLATE NOCASE ORDER BY tidal_id IS NULL LIMIT 1",
+        params![name.trim()],
+        |row| row.get::<_, String>(0)
+    ).optional()?;
+
+    if let Some(id) = existing {
+        return Ok(id);
+    }
+
+    connection.execute("INSERT OR IGNORE INTO artists (id, name) VALUES (?1, ?2)", params![local_id, name.trim()])?;
+
+    Ok(local_id)
+}
+
+fn artist_from_row(row:&Row) -> rusqlite::Result<LocalArtist> {
+    Ok(LocalArtist {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        tidal_id: row.get::<_, Option<i64>>("tidal_id")?.map(|tidal_id| tidal_id as usize),
+        picture: row.get("picture")?
+    })
+}
+
+fn insert_song(connection:&Connection, song:&Song) -> rusqlite::Result<()> {
+    let tidal_track = song.tidal_track.as_ref().and_then(|track| serde_json::to_string(track).ok());
+    let artist_id = upsert_artist(connection, &song.artist, song.tidal_track.as_ref())?;
+
+    connection.execute(
+        "INSERT INTO songs (id, path, title, artist, album, track_number, duration, cover, tidal_id, tidal_track, added_at, artist_id, lossless) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+         ON CONFLICT(id) DO UPDATE SET path = excluded.path, title = excluded.title, artist = excluded.artist, album = excluded.album, track_number = excluded.track_number,
+         duration = excluded.duration, cover = excluded.cover, tidal_id = excluded.tidal_id, tidal_track = excluded.tidal_track, added_at = COALESCE(songs.added_at, excluded.added_at),
+         artist_id = excluded.artist_id, lossless = COALESCE(excluded.lossless, songs.lossless)",
+        params![
+            song.id,
+            song.path.to_string_lossy(),
+            song.title,
+            song.artist,
+            song.album,
+            song.track_number,
+            song.duration.map(|duration| duration as i64),
+            song.cover,
+            song.tidal_track.as_ref().map(|track| track.id as i64),
+            tidal_track,
+            song.added_at.unwrap_or(time::now()) as i64,
+            artist_id,
+            song.lossless
+        ]
+    )?;
+
+    Ok(())
+}
+
+fn insert_playlist(connection:&Connection, playlist:&Playlist) -> rusqlite::Result<()> {
+    let rules = match &playlist.rules {
+        Some(rules) => Some(serde_json::to_string(rules).map_err(to_json_error)?),
+        None => None
+    };
+
+    connection.execute(
+        "INSERT INTO playlists (id, name, image, rules, description) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            playlist.id,
+            playlist.name,
+            playlist.image.as_ref().map(|image| image.to_string_lossy().to_string()),
+            rules,
+            playlist.description
+        ]
+    )?;
+
+    let key = connection.last_insert_rowid();
+    push_ids_to_playlist(connection, key, &playlist.songs)
+}
+
+fn push_ids_to_playlist(connection:&Connection, key:i64, ids:&Vec<String>) -> rusqlite::Result<()> {
+    let mut statement = connection.prepare_cached(
+        "INSERT OR IGNORE INTO playlist_songs (playlist, song, position)
+         VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM playlist_songs WHERE playlist = ?1))"
+    )?;
+
+    for id in ids {
+        statement.execute(params![key, id])?;
+    }
+
+    Ok(())
+}
+
+fn insert_album(connection:&Connection, album:&Album, ids:&Vec<String>) -> rusqlite::Result<()> {
+    let data = serde_json::to_string(album).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+    connection.execute(
+        "INSERT INTO albums (id, data) VALUES (?1, ?2) ON CONFLICT(id) DO UPDATE SET data = excluded.data",
+        params![album.id as i64, data]
+    )?;
+
+    connection.execute("DELETE FROM album_tracks WHERE album = ?1", params![album.id as i64])?;
+
+    let mut statement = connection.prepare_cached("INSERT INTO album_tracks (album, position, song) VALUES (?1, ?2, ?3)")?;
+    for (position, id) in ids.iter().enumerate() {
+        statement.execute(params![album.id as i64, position as i64, id])?;
+    }
+
+    Ok(())
+}
+
+fn find_playlist(connection:&Connection, id:&str) -> rusqlite::Result<Option<i64>> {
+    connection.query_row("SELECT key FROM playlists WHERE id = ?1", params![id], |row| row.get(0))
+        .optional()
+}
+
+//playlists and albums a song belongs to, as (key, position), kept to put it back where it was
+#[derive(Clone, Debug, Default)]
+pub struct SongMemberships {
+    playlists:Vec<(i64, i64)>,
+    albums:Vec<(i64, i64)>
+}
+
+fn query_memberships(connection:&Connection, song:&Song) -> rusqlite::Result<SongMemberships> {
+    let playlists = {
+        let mut statement = connection.prepare_cached("SELECT playlist, position FROM playlist_songs WHERE song = ?1")?;
+        let rows = statement.query_map(params![song.id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<(i64, i64)>>>()?
+    };
+
+    let albums = {
+        let mut statement = connection.prepare_cached("SELECT album, position FROM album_tracks WHERE song = ?1")?;
+        let rows = statement.query_map(params![song.id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<(i64, i64)>>>()?
+    };
+
+    Ok(SongMemberships { playlists, albums })
+}
+
+//only the playlists and albums that still exist get the song back
+fn insert_memberships(connection:&Connection, song:&Song, memberships:&SongMemberships) -> rusqlite::Result<()> {
+    for (playlist, position) in &memberships.playlists {
+        connection.execute(
+            "INSERT OR IGNORE INTO playlist_songs (playlist, song, position) SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM playlists WHERE key = ?1)",
+            params![playlist, song.id, position]
+        )?;
+    }
+
+    for (album, position) in &memberships.albums {
+        connection.execute(
+            "INSERT OR IGNORE INTO album_tracks (album, position, song) SELECT ?1, ?2, ?3 WHERE EXISTS (SELECT 1 FROM albums WHERE id = ?1)",
+            params![album, position, song.id]
+        )?;
+    }
+
+    Ok(())
+}
+
+const PLAYLIST_COLUMNS: &str = "id, name, description, rules IS NOT NULL, folder, image, cover";
+
+fn descriptor_from_row(row:&Row) -> rusqlite::Result<PlaylistDescriptor> {
+    Ok(PlaylistDescriptor {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        image: row.get::<_, Option<String>>(5)?.map(PathBuf::from),
+        description: row.get(2)?,
+        smart: row.get(3)?,
+        folder: row.get(4)?,
+        cover: row.get(6)?
+    })
+}
+
+//the mosaic is generated again the next time the playlist is shown
+fn clear_playlist_cover(connection:&Connection, key:i64) -> rusqlite::Result<()> {
+    connection.execute("UPDATE playlists SET cover = NULL WHERE key = ?1", params![key])?;
+
+    Ok(())
+}
+
+fn folder_from_row(row:&Row) -> rusqlite::Result<PlaylistFolder> {
+    Ok(PlaylistFolder {
+        id: row.get("id")?,
+        name: row.get("name")?,
+        parent: row.get("parent")?,
+        expanded: row.get("expanded")?
+    })
+}
+
+fn find_playlist_rules(connection:&Connection, key:i64) -> rusqlite::Result<Option<SmartRules>> {
+    let rules = connection.query_row("SELECT rules FROM playlists WHERE key = ?1", params![key], |row| row.get::<_, Option<String>>(0))?;
+
+    match rules {
+        Some(rules) => Ok(Some(serde_json::from_str(&rules).map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?)),
+        None => Ok(None)
+    }
+}
+
+//a read that fails is logged and treated as empty, the gui keeps going with what it has
+fn read_or_default<T:Default>(what:&str, result:rusqlite::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        println!("Failed to read {} : {}", what, e);
+        T::default()
+    })
+}
+
+fn query_songs(connection:&Connection) -> Vec<Song> {
+    let statement = connection.prepare_cached("SELECT * FROM songs");
+
+    read_or_default("songs", statement.and_then(|mut statement| statement
+        .query_map([], song_from_row)
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Song>>>())
+    ))
+}
+
+fn query_stats(connection:&Connection) -> HashMap<String, SongStats> {
+    let statement = connection.prepare_cached("SELECT song, COUNT(*) FILTER (WHERE event = 'complete'), MAX(timestamp) FROM play_history GROUP BY song");
+
+    let rows = read_or_default("play stats", statement.and_then(|mut statement| statement
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, SongStats {
+            play_count: row.get::<_, i64>(1)? as u64,
+            last_played: row.get::<_, Option<i64>>(2)?.map(|x| x as u64)
+        })))
+        .and_then(|rows| rows.collect::<rusqlite::Result<Vec<(String, SongStats)>>>())
+    ));
+
+    rows.into_iter().collect()
+}
+
+//songs of a playlist, smart playlists are evaluated against the whole library
+fn query_playlist_songs(connection:&Connection, key:i64) -> rusqlite::Result<Vec<Song>> {
+    if let Some(rules) = find_playlist_rules(connection, key)? {
+        return Ok(rules.evaluate(query_songs(connection), &query_stats(connection)));
+    }
+
+    let mut statement = connection.prepare_cached(
+        "SELECT songs.* FROM playlist_songs JOIN songs ON songs.id = playlist_songs.song WHERE playlist_songs.playlist = ?1 ORDER BY playlist_songs.position"
+    )?;
+
+    let songs = statement.query_map(params![key], song_from_row)?.collect::<rusqlite::Result<Vec<Song>>>();
+    songs
+}
+
 impl AlbumController {
-    pub fn add_album(&self, album: &Album, tracks:Vec<Song>) {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        
-        let hashed_songs = tracks.iter().map(|song| CachedHashSong::from(song).hash).collect::<Vec<u64>>();
-
-        let album_with_tracks = AlbumHashed {
-            album: album.clone(),
-            tracks: hashed_songs,
-        };
+    pub fn add_album(&self, album: &Album, tracks:Vec<Song>) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
 
-        if data.albums.contains(&album_with_tracks) {
-            data.albums.iter_mut().find(|x| x.album.id == album_with_tracks.album.id).unwrap().tracks = album_with_tracks.tracks.clone();
-        } else {
-            data.albums.push(album_with_tracks);
-        }
+        let song_ids = tracks.iter().map(|song| song.id.clone()).collect::<Vec<String>>();
+
+        let transaction = connection.transaction()?;
+        insert_album(&transaction, album, &song_ids)?;
+        transaction.commit()
     }
 
-    pub fn get_albums(&self, app:Arc<AppImpl>) -> Vec<AlbumWithSongs> {
-        let data = self.database.data
-        .lock()
-        .unwrap();
+    pub fn get_albums(&self) -> Vec<AlbumWithSongs> {
+        let albums = {
+            let connection = self.database.connection();
+            let statement = connection.prepare_cached("SELECT data FROM albums ORDER BY rowid");
+
+            read_or_default("albums", statement.and_then(|mut statement| statement
+                .query_map([], |row| row.get::<_, String>(0))
+                .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+            ))
+        };
 
-            data.albums
-            .iter()
+        albums.iter()
+            .filter_map(|json| serde_json::from_str::<Album>(json).ok())
             .map(|album| {
-                AlbumWithSongs { album: album.album.clone(), tracks: album.tracks.iter().filter_map(|item| data.tracks.0.get(&item).cloned()).collect::<Vec<Song>>() }
+                let tracks = self.get_album_tracks(&album);
+                AlbumWithSongs { album, tracks }
             })
             .collect::<Vec<AlbumWithSongs>>()
     }
 
-    pub fn get_album_tracks(&self, app:Arc<AppImpl>, album: &Album) -> Vec<Song>
+    pub fn get_album_tracks(&self, album: &Album) -> Vec<Song>
+    {
+        self.resolve_album(album)
+    }
+
+    pub fn get_album_song_ids(&self, album:&Album) -> Vec<String>
     {
-        let data = self.database.data
-        .lock()
-        .unwrap();
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached("SELECT song FROM album_tracks WHERE album = ?1 ORDER BY position");
 
-        let album = data.albums.iter().find(|x| x.album.id == album.id).unwrap();
-        album.tracks.iter().filter_map(|item| data.tracks.0.get(&item).cloned()).collect::<Vec<Song>>()
+        read_or_default("album tracks", statement.and_then(|mut statement| statement
+            .query_map(params![album.id as i64], |row| row.get::<_, String>(0))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<String>>>())
+        ))
     }
 
     pub fn resolve_album(&self, album:&Album) -> Vec<Song>
     {
-        let data = self.database.data
-        .lock()
-        .unwrap();
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached(
+            "SELECT songs.* FROM album_tracks JOIN songs ON songs.id = album_tracks.song WHERE album_tracks.album = ?1 ORDER BY album_tracks.position"
+        );
+
+        read_or_default("album tracks", statement.and_then(|mut statement| statement
+            .query_map(params![album.id as i64], song_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Song>>>())
+        ))
+    }
+
+    //the songs stay in the library
+    pub fn set_track_count(&self, album:&Album, track_count:usize) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("INSERT OR REPLACE INTO album_track_counts (album, track_count) VALUES (?1, ?2)", params![album.id as i64, track_count as i64])?;
+
+        Ok(())
+    }
 
-        let album = data.albums.iter().find(|x| x.album.id == album.id).unwrap();
-        album.tracks.iter().filter_map(|item| data.tracks.0.get(&item).cloned()).collect::<Vec<Song>>()
+    pub fn get_track_count(&self, album:&Album) -> Option<usize> {
+        let connection = self.database.connection();
+        connection.query_row("SELECT track_count FROM album_track_counts WHERE album = ?1", params![album.id as i64], |row| row.get::<_, i64>(0))
+            .optional()
+            .unwrap_or_else(|e| {
+                println!("Failed to read album track count : {}", e);
+                None
+            })
+            .map(|track_count| track_count as usize)
     }
 
+    pub fn remove_album(&self, album:&Album) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
 
+        transaction.execute("DELETE FROM album_tracks WHERE album = ?1", params![album.id as i64])?;
+        transaction.execute("DELETE FROM albums WHERE id = ?1", params![album.id as i64])?;
+
+        transaction.commit()
+    }
 }
 
 impl PlaylistController {
-    pub fn add_playlist(&self, playlist: &Playlist) {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
+    pub fn add_playlist(&self, playlist: &Playlist) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
 
-        data.playlists.push(playlist.clone());
+        let transaction = connection.transaction()?;
+        insert_playlist(&transaction, playlist)?;
+        transaction.commit()
     }
 
-    pub fn push_to_playlist(&self, playlist: &PlaylistDescriptor, songs: &Vec<Song>) {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        if let Some(playlist_index) =  data.playlists.iter().position(|p| p.name == playlist.name) {
-            songs.iter().for_each(|song| {
-                let cached_song = CachedHashSong::from(song);
-                if !data.playlists[playlist_index].songs.contains(&cached_song.hash) {
-                    data.playlists[playlist_index].songs.push(cached_song.hash);
-                }
-            });
+    pub fn push_to_playlist(&self, playlist: &PlaylistDescriptor, songs: &Vec<Song>) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        if let Some(key) = find_playlist(&transaction, &playlist.id)? {
+            let song_ids = songs.iter().map(|song| song.id.clone()).collect::<Vec<String>>();
+            push_ids_to_playlist(&transaction, key, &song_ids)?;
+            clear_playlist_cover(&transaction, key)?;
         }
+
+        transaction.commit()
+    }
+
+    pub fn get_playlist_song_ids(&self, descriptor: &PlaylistDescriptor) -> Vec<String> {
+        self.unhash_playlist_songs(descriptor)
+            .map(|playlist| playlist.songs.into_iter().map(|song| song.id).collect())
+            .unwrap_or_default()
     }
 
     pub fn unhash_playlist_songs(&self, descriptor: &PlaylistDescriptor) -> Option<DecodedPlaylist> {
-        let data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        let playlist = data.playlists.iter().find(|p| p.name == descriptor.name)?;
-        let songs = playlist.songs.iter().filter_map(|item| data.tracks.0.get(&item).cloned()).collect::<Vec<Song>>();
-        
+        let connection = self.database.connection();
+        let key = read_or_default("playlist", find_playlist(&connection, &descriptor.id))?;
+
         Some(DecodedPlaylist {
             descriptor: descriptor.clone(),
-            songs
+            songs: read_or_default("playlist songs", query_playlist_songs(&connection, key).map(Some))?
         })
     }
 
+    pub fn get_rules(&self, descriptor: &PlaylistDescriptor) -> Option<SmartRules> {
+        let connection = self.database.connection();
+        let key = read_or_default("playlist", find_playlist(&connection, &descriptor.id))?;
+
+        read_or_default("playlist rules", find_playlist_rules(&connection, key))
+    }
+
+    pub fn set_rules(&self, descriptor: &PlaylistDescriptor, rules: &SmartRules) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        let rules = serde_json::to_string(rules).map_err(to_json_error)?;
+
+        if let Some(key) = find_playlist(&connection, &descriptor.id)? {
+            connection.execute("UPDATE playlists SET rules = ?1 WHERE key = ?2", params![rules, key])?;
+            clear_playlist_cover(&connection, key)?;
+        }
+
+        Ok(())
+    }
+
     pub fn get_playlists(&self) -> Vec<PlaylistDescriptor> {
-        self.database.data
-            .lock()
-            .unwrap()
-            .playlists.iter().map(|playlist| {
-                PlaylistDescriptor {
-                    id: playlist.id.clone(),
-                    name: playlist.name.clone(),
-                    image: None //TODO: impl image
-                }
-            }).collect::<Vec<PlaylistDescriptor>>()
-            .clone()
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached(&format!("SELECT {} FROM playlists ORDER BY key", PLAYLIST_COLUMNS));
+
+        read_or_default("playlists", statement.and_then(|mut statement| statement
+            .query_map([], descriptor_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<PlaylistDescriptor>>>())
+        ))
+    }
+
+    pub fn get_playlist(&self, id:&str) -> Option<PlaylistDescriptor> {
+        let connection = self.database.connection();
+        let playlist = connection.query_row(&format!("SELECT {} FROM playlists WHERE id = ?1", PLAYLIST_COLUMNS), params![id], descriptor_from_row).optional();
+        read_or_default("playlist", playlist)
     }
 
-    pub fn remove_from_playlist(&self, playlist:&PlaylistDescriptor, song:&Song) {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        if let Some(playlist_index) =  data.playlists.iter().position(|p| p.name == playlist.name) {
-            let cached_song = CachedHashSong::from(song);
-            if let Some(song_index) = data.playlists[playlist_index].songs.iter().position(|x| x == &cached_song.hash) {
-                data.playlists[playlist_index].songs.remove(song_index);
+    pub fn rename_playlist(&self, playlist:&PlaylistDescriptor, name:&str) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlists SET name = ?1 WHERE id = ?2", params![name, playlist.id])?;
+
+        Ok(())
+    }
+
+    pub fn set_description(&self, playlist:&PlaylistDescriptor, description:Option<String>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlists SET description = ?1 WHERE id = ?2", params![description, playlist.id])?;
+
+        Ok(())
+    }
+
+    //moves the song at index `from` to index `to`, positions are rewritten so they stay contiguous
+    //false when there was nothing to move
+    pub fn move_song(&self, playlist:&PlaylistDescriptor, from:usize, to:usize) -> rusqlite::Result<bool> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let key = find_playlist(&transaction, &playlist.id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+
+        let mut song_ids = {
+            let mut statement = transaction.prepare_cached("SELECT song FROM playlist_songs WHERE playlist = ?1 ORDER BY position")?;
+            let rows = statement.query_map(params![key], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<Vec<String>>>()?
+        };
+
+        if from == to || from >= song_ids.len() || to >= song_ids.len() {
+            return Ok(false);
+        }
+
+        let song_id = song_ids.remove(from);
+        song_ids.insert(to, song_id);
+
+        for (position, song_id) in song_ids.iter().enumerate() {
+            transaction.execute("UPDATE playlist_songs SET position = ?1 WHERE playlist = ?2 AND song = ?3", params![position as i64, key, song_id])?;
+        }
+
+        clear_playlist_cover(&transaction, key)?;
+        transaction.commit()?;
+
+        Ok(true)
+    }
+
+    //copies the songs, rules and description into a new playlist, returns it
+    pub fn duplicate_playlist(&self, playlist:&PlaylistDescriptor, name:&str) -> rusqlite::Result<PlaylistDescriptor> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let key = find_playlist(&transaction, &playlist.id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        let id = Playlist::generate_id();
+
+        transaction.execute(
+            "INSERT INTO playlists (id, name, image, rules, description, folder, cover) SELECT ?1, ?2, image, rules, description, folder, cover FROM playlists WHERE key = ?3",
+            params![id, name, key]
+        )?;
+
+        let new_key = transaction.last_insert_rowid();
+        transaction.execute(
+            "INSERT INTO playlist_songs (playlist, song, position) SELECT ?1, song, position FROM playlist_songs WHERE playlist = ?2",
+            params![new_key, key]
+        )?;
+
+        let descriptor = transaction.query_row(&format!("SELECT {} FROM playlists WHERE key = ?1", PLAYLIST_COLUMNS), params![new_key], descriptor_from_row)?;
+
+        transaction.commit()?;
+
+        Ok(descriptor)
+    }
+
+    pub fn set_image(&self, playlist:&PlaylistDescriptor, image:Option<&Path>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlists SET image = ?1 WHERE id = ?2", params![image.map(|image| image.to_string_lossy().to_string()), playlist.id])?;
+
+        Ok(())
+    }
+
+    pub fn set_cover(&self, playlist:&PlaylistDescriptor, cover:Option<&str>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlists SET cover = ?1 WHERE id = ?2", params![cover, playlist.id])?;
+
+        Ok(())
+    }
+
+    pub fn get_folders(&self) -> Vec<PlaylistFolder> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached("SELECT * FROM playlist_folders ORDER BY name");
+
+        read_or_default("playlist folders", statement.and_then(|mut statement| statement
+            .query_map([], folder_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<PlaylistFolder>>>())
+        ))
+    }
+
+    pub fn add_folder(&self, name:&str, parent:Option<&str>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("INSERT INTO playlist_folders (id, name, parent) VALUES (?1, ?2, ?3)", params![Playlist::generate_id(), name, parent])?;
+
+        Ok(())
+    }
+
+    pub fn rename_folder(&self, folder:&PlaylistFolder, name:&str) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlist_folders SET name = ?1 WHERE id = ?2", params![name, folder.id])?;
+
+        Ok(())
+    }
+
+    pub fn set_folder_expanded(&self, folder:&PlaylistFolder, expanded:bool) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlist_folders SET expanded = ?1 WHERE id = ?2", params![expanded, folder.id])?;
+
+        Ok(())
+    }
+
+    //subfolders are deleted too, the playlists inside go back to the root
+    pub fn remove_folder(&self, folder:&PlaylistFolder) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("DELETE FROM playlist_folders WHERE id = ?1", params![folder.id])?;
+
+        Ok(())
+    }
+
+    pub fn move_playlist_to_folder(&self, playlist:&PlaylistDescriptor, folder:Option<&str>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE playlists SET folder = ?1 WHERE id = ?2", params![folder, playlist.id])?;
+
+        Ok(())
+    }
+
+    //a folder can't be moved inside itself or one of its subfolders
+    pub fn move_folder(&self, folder:&PlaylistFolder, parent:Option<&str>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+
+        if let Some(parent) = parent {
+            let is_descendant = connection.query_row(
+                "WITH RECURSIVE descendants(id) AS (SELECT ?1 UNION SELECT playlist_folders.id FROM playlist_folders JOIN descendants ON playlist_folders.parent = descendants.id)
+                 SELECT EXISTS (SELECT 1 FROM descendants WHERE id = ?2)",
+                params![folder.id, parent],
+                |row| row.get::<_, bool>(0)
+            )?;
+
+            if is_descendant {
+                return Ok(());
+            }
+        }
+
+        connection.execute("UPDATE playlist_folders SET parent = ?1 WHERE id = ?2", params![parent, folder.id])?;
+
+        Ok(())
+    }
+
+    //songs of every playlist of the folder and its subfolders, in order and without duplicates
+    pub fn get_folder_songs(&self, folder:&PlaylistFolder) -> Vec<Song> {
+        let connection = self.database.connection();
+
+        let keys = read_or_default("playlist folder", connection.prepare_cached(
+            "WITH RECURSIVE descendants(id) AS (SELECT ?1 UNION SELECT playlist_folders.id FROM playlist_folders JOIN descendants ON playlist_folders.parent = descendants.id)
+             SELECT key FROM playlists WHERE folder IN (SELECT id FROM descendants) ORDER BY key"
+        ).and_then(|mut statement| {
+            let rows = statement.query_map(params![folder.id], |row| row.get::<_, i64>(0))?;
+            rows.collect::<rusqlite::Result<Vec<i64>>>()
+        }));
+
+        let mut songs:Vec<Song> = vec![];
+        for key in keys {
+            for song in read_or_default("playlist songs", query_playlist_songs(&connection, key)) {
+                if !songs.iter().any(|x| x.id == song.id) {
+                    songs.push(song);
+                }
             }
         }
+
+        songs
+    }
+
+    //index of the song in the playlist, the positions themselves can have gaps
+    pub fn get_song_position(&self, playlist:&PlaylistDescriptor, song:&Song) -> Option<i64> {
+        let connection = self.database.connection();
+        let key = read_or_default("playlist", find_playlist(&connection, &playlist.id))?;
+
+        let position = connection.query_row("SELECT position FROM playlist_songs WHERE playlist = ?1 AND song = ?2", params![key, song.id], |row| row.get::<_, i64>(0)).optional();
+        let position = read_or_default("song position", position)?;
+
+        let index = connection.query_row("SELECT COUNT(*) FROM playlist_songs WHERE playlist = ?1 AND position < ?2", params![key, position], |row| row.get(0)).map(Some);
+        read_or_default("song position", index)
+    }
+
+    //puts the song back at the index it had, the positions are rewritten so the songs after it keep their order
+    pub fn insert_into_playlist(&self, playlist:&PlaylistDescriptor, song:&Song, position:i64) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let Some(key) = find_playlist(&transaction, &playlist.id)? else {
+            return Ok(());
+        };
+
+        let mut song_ids = {
+            let mut statement = transaction.prepare_cached("SELECT song FROM playlist_songs WHERE playlist = ?1 ORDER BY position")?;
+            let rows = statement.query_map(params![key], |row| row.get::<_, String>(0))?;
+            rows.collect::<rusqlite::Result<Vec<String>>>()?
+        };
+
+        if song_ids.contains(&song.id) {
+            return Ok(());
+        }
+
+        let index = (position.max(0) as usize).min(song_ids.len());
+        song_ids.insert(index, song.id.clone());
+
+        transaction.execute("INSERT INTO playlist_songs (playlist, song, position) VALUES (?1, ?2, ?3)", params![key, song.id, index as i64])?;
+
+        for (position, song_id) in song_ids.iter().enumerate() {
+            transaction.execute("UPDATE playlist_songs SET position = ?1 WHERE playlist = ?2 AND song = ?3", params![position as i64, key, song_id])?;
+        }
+
+        clear_playlist_cover(&transaction, key)?;
+        transaction.commit()
+    }
+
+    pub fn remove_from_playlist(&self, playlist:&PlaylistDescriptor, song:&Song) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        if let Some(key) = find_playlist(&connection, &playlist.id)? {
+            connection.execute("DELETE FROM playlist_songs WHERE playlist = ?1 AND song = ?2", params![key, song.id])?;
+            clear_playlist_cover(&connection, key)?;
+        }
+
+        Ok(())
     }
 
-    pub fn remove_playlist(&self, playlist: &PlaylistDescriptor) -> Result<(), std::io::Error> {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        if let Some(index) = data.playlists.iter().position(|p| p.name == playlist.name) {
-            data.playlists.remove(index);
+    pub fn remove_playlist(&self, playlist: &PlaylistDescriptor) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        if let Some(key) = find_playlist(&connection, &playlist.id)? {
+            connection.execute("DELETE FROM playlists WHERE key = ?1", params![key])?;
         } else {
-            return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Playlist not found"));
+            return Err(rusqlite::Error::QueryReturnedNoRows);
         }
 
         Ok(())
@@ -322,39 +880,394 @@ impl PlaylistController {
 }
 
 impl SongController {
-    pub fn add_song(&self, song: Song) {
-        let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-        let cached_song = CachedHashSong::from(&song);
-        data.tracks.0.insert(cached_song.hash, cached_song.song);
+    //bumped by triggers every time a song is added, changed or removed, used to know when the search index is outdated
+    pub fn revision(&self) -> Option<i64> {
+        let connection = self.database.connection();
+        read_or_default("songs revision", connection.query_row("SELECT value FROM songs_revision", [], |row| row.get::<_, i64>(0)).map(Some))
+    }
+
+    pub fn add_song(&self, song: Song) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        insert_song(&connection, &song)
     }
 
     pub fn get_songs(&self) -> Vec<Song> {
-        self.database.data
-            .lock()
-            .unwrap()
-            .tracks()
+        let connection = self.database.connection();
+        query_songs(&connection)
+    }
+
+    pub fn get_song(&self, id:&str) -> Option<Song> {
+        let connection = self.database.connection();
+        read_or_default("song", connection.query_row("SELECT * FROM songs WHERE id = ?1", params![id], song_from_row).optional())
+    }
+
+    pub fn get_song_by_path(&self, path:&Path) -> Option<Song> {
+        let connection = self.database.connection();
+        read_or_default("song", connection.query_row("SELECT * FROM songs WHERE path = ?1", params![path.to_string_lossy()], song_from_row).optional())
     }
 
-    pub fn remove_song(&self, song: Song) -> Result<(), std::io::Error> {
+    pub fn set_paths(&self, paths:&Vec<(Song, PathBuf)>) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
 
-        let cached_song = CachedHashSong::from(&song);
         {
-            let mut data: std::sync::MutexGuard<'_, DatabaseDataContainer> = self.database.data.lock().unwrap();
-           if data.tracks.0.remove_entry(&cached_song.hash).is_none() {
-                return Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Song not found"));
+            let mut statement = transaction.prepare_cached("UPDATE songs SET path = ?1 WHERE id = ?2")?;
+            for (song, path) in paths {
+                statement.execute(params![path.to_string_lossy(), song.id])?;
             }
         }
 
+        transaction.commit()
+    }
+
+    //the song at `from` or every song under it when it is a folder, substr instead of LIKE since paths can hold % and _
+    pub fn relocate_path(&self, from:&Path, to:&Path) -> rusqlite::Result<usize> {
+        let connection = self.database.connection();
+        let from = from.to_string_lossy();
+        let folder = format!("{}{}", from, std::path::MAIN_SEPARATOR);
+
+        connection.execute(
+            "UPDATE songs SET path = ?2 || substr(path, length(?1) + 1) WHERE path = ?1 OR substr(path, 1, length(?3)) = ?3",
+            params![from, to.to_string_lossy(), folder]
+        )
+    }
+
+    pub fn remove_path(&self, path:&Path) -> rusqlite::Result<usize> {
+        let connection = self.database.connection();
+        let path = path.to_string_lossy();
+        let folder = format!("{}{}", path, std::path::MAIN_SEPARATOR);
+
+        connection.execute("DELETE FROM songs WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2", params![path, folder])
+    }
+
+    pub fn get_song_by_track(&self, track:&Track) -> Option<Song> {
+        let connection = self.database.connection();
+        let songs = connection.prepare_cached("SELECT * FROM songs WHERE tidal_id = ?1").and_then(|mut statement| statement
+            .query_map(params![track.id as i64], song_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Song>>>())
+        );
+        let songs = read_or_default("songs", songs);
+
+        songs.into_iter().find(|song| song.tidal_track.as_ref() == Some(track))
+    }
+
+    pub fn get_memberships(&self, song:&Song) -> rusqlite::Result<SongMemberships> {
+        let connection = self.database.connection();
+        query_memberships(&connection, song)
+    }
+
+    //puts back a song removed from the library, in the playlists and albums it was in
+    pub fn restore_song(&self, song:&Song, memberships:&SongMemberships) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        insert_song(&transaction, song)?;
+        insert_memberships(&transaction, song, memberships)?;
+
+        transaction.commit()
+    }
+
+    pub fn remove_song(&self, song: Song) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+
+        if connection.execute("DELETE FROM songs WHERE id = ?1", params![song.id])? == 0 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
         Ok(())
     }
 }
 
 
 
+fn to_json_error(e:serde_json::Error) -> rusqlite::Error {
+    rusqlite::Error::ToSqlConversionFailure(Box::new(e))
+}
+
+impl TrashController {
+    //removes the song from the library and remembers its playlists and albums so it can be restored
+    pub fn add(&self, song:&Song, trash_path:&Path, deleted_at:u64) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let memberships = query_memberships(&transaction, song)?;
+
+        transaction.execute(
+            "INSERT OR REPLACE INTO trash (id, song, trash_path, deleted_at, playlists, albums) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                song.id,
+                serde_json::to_string(song).map_err(to_json_error)?,
+                trash_path.to_string_lossy(),
+                deleted_at as i64,
+                serde_json::to_string(&memberships.playlists).map_err(to_json_error)?,
+                serde_json::to_string(&memberships.albums).map_err(to_json_error)?
+            ]
+        )?;
+
+        transaction.execute("DELETE FROM songs WHERE id = ?1", params![song.id])?;
+
+        transaction.commit()
+    }
+
+    pub fn get_entries(&self) -> Vec<TrashEntry> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached("SELECT song, trash_path, deleted_at FROM trash ORDER BY deleted_at DESC");
+
+        let rows = read_or_default("trash", statement.and_then(|mut statement| statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<(String, String, i64)>>>())
+        ));
+
+        rows.into_iter().filter_map(|(song, trash_path, deleted_at)| {
+            Some(TrashEntry {
+                song: serde_json::from_str(&song).ok()?,
+                trash_path: PathBuf::from(trash_path),
+                deleted_at: deleted_at as u64
+            })
+        }).collect()
+    }
+
+    //puts the song back in the library, with the playlists and albums that still exist
+    pub fn restore(&self, entry:&TrashEntry) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let (playlists, albums) = transaction.query_row("SELECT playlists, albums FROM trash WHERE id = ?1", params![entry.song.id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let memberships = SongMemberships {
+            playlists: serde_json::from_str(&playlists).unwrap_or_default(),
+            albums: serde_json::from_str(&albums).unwrap_or_default()
+        };
+
+        insert_song(&transaction, &entry.song)?;
+        insert_memberships(&transaction, &entry.song, &memberships)?;
+
+        transaction.execute("DELETE FROM trash WHERE id = ?1", params![entry.song.id])?;
+
+        transaction.commit()
+    }
+
+    pub fn remove(&self, entry:&TrashEntry) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("DELETE FROM trash WHERE id = ?1", params![entry.song.id])?;
+
+        Ok(())
+    }
+}
+
+impl ArtistController {
+    //artists without songs left in the library are not listed
+    pub fn get_artists(&self) -> Vec<LocalArtist> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached(
+            "SELECT * FROM artists WHERE EXISTS (SELECT 1 FROM songs WHERE songs.artist_id = artists.id) ORDER BY name COLLATE NOCASE"
+        );
+
+        read_or_default("artists", statement.and_then(|mut statement| statement
+            .query_map([], artist_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<LocalArtist>>>())
+        ))
+    }
+
+    pub fn get_artist_of_song(&self, song:&Song) -> Option<LocalArtist> {
+        let connection = self.database.connection();
+        let artist = connection.query_row(
+            "SELECT artists.* FROM songs JOIN artists ON artists.id = songs.artist_id WHERE songs.id = ?1",
+            params![song.id],
+            artist_from_row
+        ).optional();
+
+        read_or_default("artist", artist)
+    }
+
+    pub fn get_artist_songs(&self, artist:&LocalArtist) -> Vec<Song> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached(
+            "SELECT * FROM songs WHERE artist_id = ?1 ORDER BY album COLLATE NOCASE, track_number, title COLLATE NOCASE"
+        );
+
+        read_or_default("artist songs", statement.and_then(|mut statement| statement
+            .query_map(params![artist.id], song_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<Song>>>())
+        ))
+    }
+}
+
+impl HistoryController {
+    pub fn add_events(&self, events:&Vec<PlayEvent>) -> rusqlite::Result<()> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        for event in events {
+            transaction.execute(
+                "INSERT INTO play_history (song, event, timestamp, listened) VALUES (?1, ?2, ?3, ?4)",
+                params![event.song_id, event.kind.as_str(), event.timestamp as i64, event.listened as i64]
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    //most recent first
+    pub fn get_events(&self, limit:usize) -> Vec<PlayEvent> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached("SELECT song, event, timestamp, listened FROM play_history ORDER BY id DESC LIMIT ?1");
+
+        let rows = read_or_default("play history", statement.and_then(|mut statement| statement
+            .query_map(params![limit as i64], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?, row.get::<_, i64>(3)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<(String, String, i64, i64)>>>())
+        ));
+
+        rows.into_iter().filter_map(|(song_id, kind, timestamp, listened)| {
+            Some(PlayEvent {
+                song_id,
+                kind: PlayEventKind::from_str(&kind)?,
+                timestamp: timestamp as u64,
+                listened: listened as u64
+            })
+        }).collect()
+    }
+
+    //a song counts as played once it has been listened to the end
+    pub fn get_stats(&self, song:&Song) -> SongStats {
+        let connection = self.database.connection();
+
+        let stats = connection.query_row(
+            "SELECT COUNT(*) FILTER (WHERE event = 'complete'), MAX(timestamp) FROM play_history WHERE song = ?1",
+            params![song.id],
+            |row| Ok(SongStats {
+                play_count: row.get::<_, i64>(0)? as u64,
+                last_played: row.get::<_, Option<i64>>(1)?.map(|x| x as u64)
+            })
+        );
+
+        read_or_default("play stats", stats)
+    }
+
+    //stats of every song that has been played, keyed by song id
+    pub fn get_all_stats(&self) -> HashMap<String, SongStats> {
+        let connection = self.database.connection();
+        query_stats(&connection)
+    }
+
+    pub fn clear(&self) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("DELETE FROM play_history", [])?;
+
+        Ok(())
+    }
+}
+
+impl DownloadController {
+    //a track queued again keeps its place in the queue
+    pub fn add(&self, track:&Track, quality:AudioQuality, playlist:Option<&Playlist>, album:Option<&Album>) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+
+        let playlist = match playlist {
+            Some(playlist) => Some(serde_json::to_string(playlist).map_err(to_json_error)?),
+            None => None
+        };
+
+        let album_data = match album {
+            Some(album) => Some(serde_json::to_string(album).map_err(to_json_error)?),
+            None => None
+        };
+
+        connection.execute(
+            "INSERT INTO download_queue (track_id, track, quality, playlist, album_id, album) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(track_id) DO UPDATE SET track = excluded.track, quality = excluded.quality, playlist = excluded.playlist,
+             album_id = excluded.album_id, album = excluded.album, paused = 0, finished = 0",
+            params![
+                track.id as i64,
+                serde_json::to_string(track).map_err(to_json_error)?,
+                serde_json::to_string(&quality).map_err(to_json_error)?,
+                playlist,
+                album.map(|album| album.id as i64),
+                album_data
+            ]
+        )?;
+
+        Ok(())
+    }
+
+    //the tracks already downloaded of an album that isn't complete yet aren't downloaded again
+    pub fn get_queue(&self) -> Vec<QueuedDownload> {
+        let connection = self.database.connection();
+        let statement = connection.prepare_cached("SELECT track, quality, playlist, album, paused FROM download_queue WHERE finished = 0 ORDER BY key");
+
+        let rows = read_or_default("download queue", statement.and_then(|mut statement| statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?, row.get::<_, bool>(4)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<(String, String, Option<String>, Option<String>, bool)>>>())
+        ));
+
+        rows.into_iter().filter_map(|(track, quality, playlist, album, paused)| {
+            Some(QueuedDownload {
+                track: serde_json::from_str(&track).ok()?,
+                quality: serde_json::from_str(&quality).ok()?,
+                playlist: playlist.and_then(|playlist| serde_json::from_str(&playlist).ok()),
+                album: album.and_then(|album| serde_json::from_str(&album).ok()),
+                paused
+            })
+        }).collect()
+    }
+
+    pub fn set_paused(&self, track:&Track, paused:bool) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE download_queue SET paused = ?1 WHERE track_id = ?2", params![paused, track.id as i64])?;
+
+        Ok(())
+    }
+
+    //the tracks of an album are kept until the album is complete, the others are done
+    pub fn finish(&self, track:&Track) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("UPDATE download_queue SET finished = 1 WHERE track_id = ?1 AND album_id IS NOT NULL", params![track.id as i64])?;
+        connection.execute("DELETE FROM download_queue WHERE track_id = ?1 AND album_id IS NULL", params![track.id as i64])?;
+
+        Ok(())
+    }
+
+    //once none of its tracks is left to download, the ones that were downloaded are returned and the album leaves the queue
+    pub fn take_album(&self, album:&Album) -> rusqlite::Result<Option<Vec<Track>>> {
+        let mut connection = self.database.connection();
+        let transaction = connection.transaction()?;
+
+        let remaining = transaction.query_row(
+            "SELECT COUNT(*) FROM download_queue WHERE album_id = ?1 AND finished = 0",
+            params![album.id as i64],
+            |row| row.get::<_, i64>(0)
+        )?;
+
+        if remaining > 0 {
+            return Ok(None);
+        }
+
+        let tracks = {
+            let mut statement = transaction.prepare("SELECT track FROM download_queue WHERE album_id = ?1 ORDER BY key")?;
+            let rows = statement.query_map(params![album.id as i64], |row| row.get::<_, String>(0))?.collect::<rusqlite::Result<Vec<String>>>()?;
+            rows.into_iter().filter_map(|track| serde_json::from_str(&track).ok()).collect::<Vec<Track>>()
+        };
+
+        transaction.execute("DELETE FROM download_queue WHERE album_id = ?1", params![album.id as i64])?;
+        transaction.commit()?;
+
+        Ok(Some(tracks))
+    }
+
+    pub fn remove(&self, track:&Track) -> rusqlite::Result<()> {
+        let connection = self.database.connection();
+        connection.execute("DELETE FROM download_queue WHERE track_id = ?1", params![track.id as i64])?;
+
+        Ok(())
+    }
+}
+
 impl DatabaseImpl {
     fn get_database_path() -> PathBuf {
         if let Ok(program_data) = std::env::var("PROGRAMDATA") {
-            let path_buf = PathBuf::from(program_data).join("Localfy").join("localfy.db");
+            let path_buf = PathBuf::from(program_data).join("Localfy").join("localfy.sqlite");
 
             if !path_buf.exists() {
                 std::fs::create_dir_all(path_buf.parent().unwrap()).unwrap();
@@ -363,13 +1276,111 @@ impl DatabaseImpl {
             return path_buf;
         }
 
+        PathBuf::from("localfy.sqlite")
+    }
+
+    fn get_legacy_database_path() -> PathBuf {
+        if let Ok(program_data) = std::env::var("PROGRAMDATA") {
+            return PathBuf::from(program_data).join("Localfy").join("localfy.db");
+        }
+
         PathBuf::from("localfy.db")
     }
 
-    pub fn hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.data.lock().unwrap().hash(&mut hasher);
-        hasher.finish()
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock().unwrap()
+    }
+
+    //set when the database couldn't be opened as is and had to be recovered
+    pub fn recovery(&self) -> Option<String> {
+        self.recovery.clone()
+    }
+
+    fn vacuum_into(connection:&Connection, destination:&Path) -> Result<(), std::io::Error> {
+        connection.execute("VACUUM INTO ?1", params![destination.to_string_lossy()])
+            .map(|_| ())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    }
+
+    fn write_compressed(connection:&Connection, path:&Path) -> Result<(), std::io::Error> {
+        let uncompressed_path = migration::backup_path(path, "uncompressed");
+        if uncompressed_path.exists() {
+            std::fs::remove_file(&uncompressed_path)?;
+        }
+
+        let result = DatabaseImpl::vacuum_into(connection, &uncompressed_path).and_then(|_| {
+            backup::write_atomically(path, |temporary_path| backup::compress_file(&uncompressed_path, temporary_path))
+        });
+
+        let _ = std::fs::remove_file(&uncompressed_path);
+        result
+    }
+
+    fn write_backup(connection:&Connection, path:&Path, compressed:bool) -> Result<PathBuf, std::io::Error> {
+        let backup_path = backup::get_backup_path(path, time::now());
+
+        if compressed {
+            DatabaseImpl::write_compressed(connection, &backup_path)?;
+        } else {
+            backup::write_atomically(&backup_path, |temporary_path| DatabaseImpl::vacuum_into(connection, temporary_path))?;
+        }
+
+        Ok(backup_path)
+    }
+
+    //a compressed library is only written back when localfy closes, until then the changes live in the working copy.
+    //the working copy is removed once it is written back, a plain library has nothing to do
+    pub fn close(&self) -> Result<(), std::io::Error> {
+        let working_path = match &self.working_path {
+            Some(working_path) => working_path,
+            None => return Ok(())
+        };
+
+        let mut connection = self.connection();
+        DatabaseImpl::write_compressed(&connection, &self.path)?;
+
+        let to_io_error = |e:rusqlite::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+        let closed = std::mem::replace(&mut *connection, Connection::open_in_memory().map_err(to_io_error)?);
+        closed.close().map_err(|(_, e)| to_io_error(e))?;
+
+        DatabaseImpl::remove_files(working_path)
+    }
+
+    pub fn backup(&self, compressed:bool) -> Result<PathBuf, std::io::Error> {
+        let backup_path = DatabaseImpl::write_backup(&self.connection(), &self.path, compressed)?;
+        backup::rotate_backups(&self.path)?;
+
+        Ok(backup_path)
+    }
+
+    pub fn get_backups(&self) -> Vec<BackupEntry> {
+        backup::list_backups(&self.path)
+    }
+
+    //the current library is backed up first so restoring can be undone, the path of that backup is returned
+    pub fn restore_backup(&self, entry:&BackupEntry) -> Result<PathBuf, std::io::Error> {
+        let to_io_error = |e:rusqlite::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+
+        let mut connection = self.connection();
+        let previous_library = DatabaseImpl::write_backup(&connection, &self.path, false)?;
+
+        if backup::is_compressed_file(&entry.path)? {
+            let uncompressed_path = migration::backup_path(&entry.path, "uncompressed");
+            let result = backup::decompress_file(&entry.path, &uncompressed_path).and_then(|_| {
+                connection.restore(DatabaseName::Main, &uncompressed_path, None::<fn(Progress)>).map_err(to_io_error)
+            });
+
+            let _ = std::fs::remove_file(&uncompressed_path);
+            result?;
+        } else {
+            connection.restore(DatabaseName::Main, &entry.path, None::<fn(Progress)>).map_err(to_io_error)?;
+        }
+
+        migration::migrate_database(&mut connection, &self.path)?;
+
+        backup::rotate_backups(&self.path)?;
+
+        Ok(previous_library)
     }
 
     pub fn read_database(
@@ -377,40 +1388,224 @@ impl DatabaseImpl {
         data: &mut DatabaseDataContainer
     ) -> Result<(), std::io::Error> {
         let mut reader = std::io::BufReader::new(file);
-        
+
         // Check if the file is empty
         if reader.fill_buf()?.is_empty() {
             println!("buffer is empty");
             return Ok(());
         }
 
-        
+        if backup::is_compressed(reader.fill_buf()?) {
+            *data = serde_json::from_reader(ZlibDecoder::new(reader))?;
+        } else {
+            *data = serde_json::from_reader(reader)?;
+        }
 
-        // let mut d = ZlibDecoder::new(reader.buffer());
-        // let mut s = String::new();
-    
-        // d.read_to_string(&mut s)?;
+        return Ok(());
+    }
 
-        let config = serde_json::from_reader(reader);
-        if let Ok(config) = config {
-            *data = config;
+    //one time import of the json database used before sqlite, the old file is kept as localfy.db.imported
+    fn import_legacy_database(connection:&mut Connection) -> Result<(), std::io::Error> {
+        let legacy_path = DatabaseImpl::get_legacy_database_path();
+        if !legacy_path.exists() {
+            return Ok(());
         }
-    
-        return Ok(());
+
+        let mut data_container = DatabaseDataContainer::default();
+        DatabaseImpl::read_database(&File::open(&legacy_path)?, &mut data_container)?;
+
+        //the json database referenced songs by the hash of the whole song, give them a stable id instead
+        let song_ids = data_container.tracks.0.iter().map(|(hash, song)| {
+            (*hash, Song::generate_id(song.tidal_track.as_ref()))
+        }).collect::<HashMap<u64, String>>();
+
+        let resolve_ids = |hashes:&Vec<u64>| -> Vec<String> {
+            hashes.iter().filter_map(|hash| song_ids.get(hash).cloned()).collect()
+        };
+
+        let to_io_error = |e:rusqlite::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+
+        let transaction = connection.transaction().map_err(to_io_error)?;
+
+        for (hash, song) in data_container.tracks.0.iter() {
+            let mut song = song.clone();
+            song.id = song_ids[hash].clone();
+
+            insert_song(&transaction, &song).map_err(to_io_error)?;
+        }
+
+        for playlist in &data_container.playlists {
+            //ids were timestamps and could collide, the name was used to find playlists back then
+            let id_is_taken = find_playlist(&transaction, &playlist.id).map_err(to_io_error)?.is_some();
+
+            let playlist = Playlist {
+                id: if id_is_taken { Playlist::generate_id() } else { playlist.id.clone() },
+                name: playlist.name.clone(),
+                image: playlist.image.clone(),
+                description: None,
+                songs: resolve_ids(&playlist.songs),
+                rules: None
+            };
+
+            insert_playlist(&transaction, &playlist).map_err(to_io_error)?;
+        }
+
+        for album in &data_container.albums {
+            insert_album(&transaction, &album.album, &resolve_ids(&album.tracks)).map_err(to_io_error)?;
+        }
+
+        transaction.commit().map_err(to_io_error)?;
+
+        std::fs::rename(&legacy_path, legacy_path.with_extension("db.imported"))?;
+
+        Ok(())
+    }
+
+    //the copies made before migrating are named after the stored library, not after the working copy
+    fn open(path:&Path, store:&Path) -> Result<Connection, std::io::Error> {
+        let to_io_error = |e:rusqlite::Error| std::io::Error::new(std::io::ErrorKind::Other, e);
+
+        let mut connection = Connection::open(path).map_err(to_io_error)?;
+        connection.execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;").map_err(to_io_error)?;
+
+        migration::migrate_database(&mut connection, store)?;
+
+        Ok(connection)
     }
 
-    pub fn new() -> Self {
+    fn get_working_path(path:&Path) -> PathBuf {
+        migration::backup_path(path, "working")
+    }
+
+    fn sidecar_files(path:&Path) -> Vec<PathBuf> {
+        ["-wal", "-shm"].iter().map(|suffix| {
+            let mut file_name = path.as_os_str().to_os_string();
+            file_name.push(suffix);
+            PathBuf::from(file_name)
+        }).collect()
+    }
+
+    fn remove_files(path:&Path) -> Result<(), std::io::Error> {
+        for file in std::iter::once(path.to_path_buf()).chain(DatabaseImpl::sidecar_files(path)) {
+            if file.exists() {
+                std::fs::remove_file(file)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    //sqlite can't work on a zlib file, a compressed library is decompressed to a working copy next to it.
+    //the stored format is detected, and the library is converted when it doesn't match the configured one
+    fn open_store(path:&Path, compress_database:bool) -> Result<(Connection, Option<PathBuf>), std::io::Error> {
+        let working_path = DatabaseImpl::get_working_path(path);
+
+        //a working copy left behind by a crash holds changes the compressed file doesn't have yet
+        let is_compressed = working_path.exists() || backup::is_compressed_file(path).unwrap_or(false);
+
+        if !is_compressed {
+            let connection = DatabaseImpl::open(path, path)?;
+            if !compress_database {
+                return Ok((connection, None));
+            }
+
+            backup::write_atomically(&working_path, |temporary_path| DatabaseImpl::vacuum_into(&connection, temporary_path))?;
+            drop(connection);
+
+            let connection = DatabaseImpl::open(&working_path, path)?;
+            DatabaseImpl::write_compressed(&connection, path)?;
+            for file in DatabaseImpl::sidecar_files(path) {
+                if file.exists() {
+                    std::fs::remove_file(file)?;
+                }
+            }
+
+            return Ok((connection, Some(working_path)));
+        }
+
+        if !working_path.exists() {
+            backup::write_atomically(&working_path, |temporary_path| backup::decompress_file(path, temporary_path))?;
+        }
+
+        let connection = DatabaseImpl::open(&working_path, path)?;
+        if compress_database {
+            return Ok((connection, Some(working_path)));
+        }
+
+        backup::write_atomically(path, |temporary_path| DatabaseImpl::vacuum_into(&connection, temporary_path))?;
+        drop(connection);
+        DatabaseImpl::remove_files(&working_path)?;
+
+        Ok((DatabaseImpl::open(path, path)?, None))
+    }
+
+    //the broken file is kept next to the new one so it can be recovered by hand
+    fn set_aside(path:&Path) -> Result<PathBuf, std::io::Error> {
+        let timestamp = time::now();
+        let broken_path = migration::backup_path(path, &format!("broken-{}", timestamp));
+
+        std::fs::rename(path, &broken_path)?;
+
+        for (file, broken_file) in DatabaseImpl::sidecar_files(path).into_iter().zip(DatabaseImpl::sidecar_files(&broken_path)) {
+            if file.exists() {
+                std::fs::rename(&file, broken_file)?;
+            }
+        }
+
+        Ok(broken_path)
+    }
+
+    pub fn new(compress_backups:bool, compress_database:bool) -> Self {
         let path = DatabaseImpl::get_database_path();
-        
+        let mut recovery = None;
+
+        let (mut connection, working_path) = match DatabaseImpl::open_store(&path, compress_database) {
+            Ok(store) => store,
+            Err(e) => {
+                println!("Failed to open database : {}", e);
+
+                let working_path = DatabaseImpl::get_working_path(&path);
+                if working_path.exists() {
+                    if let Err(e) = DatabaseImpl::set_aside(&working_path) {
+                        println!("Failed to set aside the working copy of the database : {}", e);
+                    }
+                }
 
-        let mut data_container = DatabaseDataContainer::default();
-        if let Ok(file) = File::options().read(true).open(&path) {
-            DatabaseImpl::read_database(&file, &mut data_container).unwrap();
+                let message = match DatabaseImpl::set_aside(&path) {
+                    Ok(broken_path) => format!("The library could not be opened ({}). It was moved to {} and a new library was created.", e, broken_path.display()),
+                    Err(_) => format!("The library could not be opened ({}). A temporary library is used until the file is fixed.", e)
+                };
+                recovery = Some(message);
+
+                DatabaseImpl::open_store(&path, compress_database)
+                    .or_else(|_| DatabaseImpl::open(Path::new(":memory:"), Path::new(":memory:")).map(|connection| (connection, None)))
+                    .expect("Failed to create database")
+            }
+        };
+
+        if let Err(e) = DatabaseImpl::import_legacy_database(&mut connection) {
+            println!("Failed to import legacy database : {}", e);
+            recovery = Some(format!("The old library could not be imported ({}). It was left untouched and will be imported again on next launch.", e));
         }
 
-        DatabaseImpl {
-            data: Arc::new(Mutex::new(data_container))
+        let is_backup_due = backup::list_backups(&path).first()
+            .map(|last_backup| time::now().saturating_sub(last_backup.created_at) > backup::BACKUP_INTERVAL)
+            .unwrap_or(true);
+
+        let database = DatabaseImpl {
+            connection: Mutex::new(connection),
+            path,
+            working_path,
+            recovery
+        };
+
+        if is_backup_due {
+            if let Err(e) = database.backup(compress_backups) {
+                println!("Failed to back up database : {}", e);
+            }
         }
+
+        database
     }
 }
 
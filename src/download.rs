@@ -1,10 +1,12 @@
-use std::{sync::{Arc, Mutex}, collections::{VecDeque, HashMap}, time::{Duration, Instant},  path::PathBuf};
+use std::{sync::{atomic::{AtomicUsize, Ordering}, Arc, Mutex}, collections::{VecDeque, HashMap}, time::{Duration, Instant},  path::{Path, PathBuf}};
 
 use tidal_rs::model::{Track, PlaybackManifest, Album, AudioQuality};
 use tokio::{sync::futures, task};
+use reqwest::StatusCode;
 use tokio::io::AsyncWriteExt;
-use futures_util::future::{self, join_all};
-use crate::{app::AppImpl, playlist::{Playlist, PlaylistDescriptor}};
+use tokio_util::sync::CancellationToken;
+use futures_util::future;
+use crate::{app::AppImpl, playlist::{Playlist, PlaylistDescriptor}, tagging};
 use crate::song::Song;
 
 #[derive(Clone)]
@@ -12,8 +14,72 @@ pub struct Download {
     pub track:Track,
     pub manifest:PlaybackManifest,
     pub path:PathBuf,
+    pub quality:AudioQuality,
     pub app:Arc<AppImpl>,
-    pub add_to_playlist:Option<Playlist>
+    pub add_to_playlist:Option<Playlist>,
+    pub album:Option<Album> //set when the track was queued with its whole album
+}
+
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const READ_TIMEOUT: Duration = Duration::from_secs(30); //a stalled connection is given up after this long without data
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DownloadError {
+    MissingUrl, //the manifest has no stream url
+    Request(String), //the server couldn't be reached or the connection dropped
+    Status(u16),
+    Io(std::io::ErrorKind, String),
+    Incomplete { received:usize, expected:usize },
+    Stopped //paused or cancelled from the downloads page
+}
+
+impl DownloadError {
+    //network problems usually go away, a missing file doesn't
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            DownloadError::Request(_) | DownloadError::Incomplete { .. } => true,
+            DownloadError::Status(status) => *status == 408 || *status == 429 || *status >= 500 || self.is_expired_url(),
+            DownloadError::MissingUrl | DownloadError::Io(_, _) | DownloadError::Stopped => false
+        }
+    }
+
+    //the stream urls are signed and expire, a new manifest is needed
+    pub fn is_expired_url(&self) -> bool {
+        match self {
+            DownloadError::Status(status) => *status == 403 || *status == 410,
+            _ => false
+        }
+    }
+}
+
+impl std::fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadError::MissingUrl => write!(f, "the stream has no url"),
+            DownloadError::Request(message) => write!(f, "network error : {}", message),
+            DownloadError::Status(status) => write!(f, "the server answered with status {}", status),
+            DownloadError::Io(_, message) => write!(f, "file error : {}", message),
+            DownloadError::Incomplete { received, expected } => write!(f, "the connection was closed after {} of {} bytes", received, expected),
+            DownloadError::Stopped => write!(f, "the download was stopped")
+        }
+    }
+}
+
+impl From<reqwest::Error> for DownloadError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) => DownloadError::Status(status.as_u16()),
+            None => DownloadError::Request(e.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Io(e.kind(), e.to_string())
+    }
 }
 
 #[derive(Clone, PartialEq)]
@@ -21,8 +87,17 @@ pub enum DownloadStatus {
     None,
     Queued,
     Downloading,
+    Retrying(usize, DownloadError), //number of the next attempt and what made the last one fail
+    Paused,
+    Cancelled,
     Finished,
-    Failed(String)
+    Failed(DownloadError)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopRequest {
+    Pause,
+    Cancel
 }
 
 impl DownloadStatus {
@@ -32,7 +107,22 @@ impl DownloadStatus {
 
     pub fn is_failed(&self) -> bool {
         match self {
-            DownloadStatus::Failed(_) => true,
+            DownloadStatus::Failed(_) | DownloadStatus::Cancelled => true,
+            _ => false
+        }
+    }
+
+    //paused downloads continue, failed and cancelled ones are retried
+    pub fn can_resume(&self) -> bool {
+        match self {
+            DownloadStatus::Paused | DownloadStatus::Cancelled | DownloadStatus::Failed(_) => true,
+            _ => false
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        match self {
+            DownloadStatus::Downloading | DownloadStatus::Retrying(_, _) => true,
             _ => false
         }
     }
@@ -44,8 +134,11 @@ impl ToString for DownloadStatus {
             DownloadStatus::None => "None".to_string(),
             DownloadStatus::Queued => "Queued".to_string(),
             DownloadStatus::Downloading => "Downloading".to_string(),
+            DownloadStatus::Retrying(attempt, error) => format!("Retrying (attempt {}) after : {}", attempt, error),
+            DownloadStatus::Paused => "Paused".to_string(),
+            DownloadStatus::Cancelled => "Cancelled".to_string(),
             DownloadStatus::Finished => "Finished".to_string(),
-            DownloadStatus::Failed(message) => format!("Failed with message : {}", message)
+            DownloadStatus::Failed(error) => format!("Failed : {}", error)
         }
     }
 
@@ -86,6 +179,8 @@ pub struct DownloadState {
     pub    progress:f32,
     pub    status:DownloadStatus,
     pub started_at:Instant,
+    token:CancellationToken, //cancelled to stop the worker that owns the download
+    stop:Option<StopRequest>
 }
 
 impl DownloadState {
@@ -97,13 +192,15 @@ impl DownloadState {
             speed: DataRate::new(0.0),
             progress: 0.0,
             status: DownloadStatus::Downloading,
-            started_at:Instant::now()
+            started_at:Instant::now(),
+            token: CancellationToken::new(),
+            stop: None
         }
     }
 }
 
 impl Download {
-    pub fn new(app:Arc<AppImpl>, track:Track, manifest:PlaybackManifest, path:Option<PathBuf>, add_to_playlist:Option<Playlist>) -> Self {
+    pub fn new(app:Arc<AppImpl>, track:Track, manifest:PlaybackManifest, path:Option<PathBuf>, quality:AudioQuality, add_to_playlist:Option<Playlist>, album:Option<Album>) -> Self {
         let path = path.expect("Path is required");
 
         Download {
@@ -111,7 +208,9 @@ impl Download {
             track,
             manifest,
             path,
-            add_to_playlist
+            quality,
+            add_to_playlist,
+            album
         }
     }
 
@@ -125,39 +224,264 @@ impl Download {
 
 
 
-    pub fn on_finished(&self) {
+    pub fn on_finished(&self, lossless:Option<bool>) {
         let database = self.app.database();
-        let song = Song::new_with_track(self.path.clone(), self.track.clone());
+        let mut song = Song::new_with_track(self.path.clone(), self.track.clone());
+        song.lossless = lossless;
         {
-            database.songs().add_song(song.clone());
+            let _ = database.songs().add_song(song.clone());
         }
 
         if let Some(playlist) = &self.add_to_playlist {
             
-            database.playlists().push_to_playlist(&PlaylistDescriptor::from(playlist.clone()), &vec![song]);
+            let _ = database.playlists().push_to_playlist(&PlaylistDescriptor::from(playlist.clone()), &vec![song]);
         }
     }
+
+    //the album is added once none of its tracks is left to download, with the ones that were downloaded
+    fn complete_album(&self) {
+        let Some(album) = &self.album else {
+            return;
+        };
+
+        let database = self.app.database();
+        let tracks = match database.downloads().take_album(album) {
+            Ok(Some(tracks)) => tracks,
+            Ok(None) => return,
+            Err(e) => {
+                println!("Failed to read the downloads of {} : {}", album.title, e);
+                return;
+            }
+        };
+
+        //a track downloaded again after the album was added joins the ones already there
+        let albums = database.albums();
+        let mut songs = albums.resolve_album(album);
+        let songs_controller = database.songs();
+
+        for track in &tracks {
+            if let Some(song) = songs_controller.get_song(&Song::generate_id(Some(track))) {
+                if !songs.iter().any(|x| x.id == song.id) {
+                    songs.push(song);
+                }
+            }
+        }
+
+        if songs.is_empty() {
+            return;
+        }
+
+        songs.sort_by_key(|song| song.tidal_track.as_ref().map(|track| (track.volume_number, track.track_number)));
+
+        if let Err(e) = albums.add_album(album, songs) {
+            println!("Failed to add the album {} : {}", album.title, e);
+        }
+    }
+}
+
+//the file is written next to its destination and only renamed once complete
+pub fn part_path(path:&Path) -> PathBuf {
+    let mut file_name = path.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+    file_name.push(".part");
+    path.with_file_name(file_name)
+}
+
+//resumes from the end of the .part file, starts over when the server ignores the range
+async fn open_part(client:&reqwest::Client, url:&str, part_path:&Path) -> Result<(reqwest::Response, tokio::fs::File, usize), DownloadError> {
+    let existing = tokio::fs::metadata(part_path).await.map(|metadata| metadata.len() as usize).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+
+    let mut response = request.send().await?;
+
+    if existing > 0 {
+        let content_range = response.headers().get(reqwest::header::CONTENT_RANGE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+
+        if response.status() == StatusCode::PARTIAL_CONTENT && content_range.starts_with(&format!("bytes {}-", existing)) {
+            let file = tokio::fs::OpenOptions::new().append(true).open(part_path).await?;
+            return Ok((response, file, existing));
+        }
+
+        //a full response can be used as is, anything else is asked again without the range
+        if response.status() != StatusCode::OK {
+            response = client.get(url).send().await?;
+        }
+    }
+
+    let response = response.error_for_status()?;
+    let file = tokio::fs::File::create(part_path).await?;
+
+    Ok((response, file, 0))
 }
 
 pub struct DownloadManager {
     download_queue:Arc<Mutex<VecDeque<Download>>>,
     download_state:Arc<Mutex<HashMap<Track, DownloadState>>>,
-    max_concurrency:usize
+    max_concurrency:usize,
+    max_retries:Arc<AtomicUsize> //read before each retry, changing it doesn't need a restart
 }
 
 impl DownloadManager {
-    pub fn new(max_concurrency: usize) -> Self {
+    pub fn new(max_concurrency: usize, max_retries: usize) -> Self {
         DownloadManager {
             download_queue:Arc::new(Mutex::new(VecDeque::new())),
             download_state:Arc::new(Mutex::new(HashMap::new())),
-            max_concurrency
+            max_concurrency,
+            max_retries:Arc::new(AtomicUsize::new(max_retries))
         }
     }
 
+    pub fn set_max_retries(&self, max_retries:usize) {
+        self.max_retries.store(max_retries, Ordering::Relaxed);
+    }
+
+    //the download is saved until it finishes or fails so it continues after a restart
     pub fn enqueue(&self, download:Download) -> () {
+        if let Err(e) = download.app.database().downloads().add(&download.track, download.quality, download.add_to_playlist.as_ref(), download.album.as_ref()) {
+            println!("Failed to save {} in the download queue : {}", download.track.title, e);
+        }
+
+        let mut state = DownloadState::new(download.clone(), 0);
+        state.status = DownloadStatus::Queued;
+        self.download_state.lock().unwrap().insert(download.track.clone(), state);
+
         self.download_queue.lock().unwrap().push_back(download);
     }
 
+    pub fn pause(&self, track:&Track) {
+        self.stop(track, StopRequest::Pause);
+    }
+
+    pub fn cancel(&self, track:&Track) {
+        self.stop(track, StopRequest::Cancel);
+    }
+
+    //a download that hasn't started is taken out of the queue, a running one is stopped by its worker
+    fn stop(&self, track:&Track, request:StopRequest) {
+        let queued = {
+            let mut queue = self.download_queue.lock().unwrap();
+            queue.iter().position(|download| &download.track == track).and_then(|position| queue.remove(position))
+        };
+
+        //the status is changed under the lock, the saved queue and the .part file once it is released
+        let stopped = {
+            let mut download_state = self.download_state.lock().unwrap();
+            let Some(state) = download_state.get_mut(track) else {
+                return;
+            };
+
+            if queued.is_some() {
+                state.status = match request {
+                    StopRequest::Pause => DownloadStatus::Paused,
+                    StopRequest::Cancel => DownloadStatus::Cancelled
+                };
+                Some(state.download.clone())
+            } else if state.status.is_active() || state.status == DownloadStatus::Queued {
+                //queued but already taken by a worker, it sees the token as soon as it starts
+                state.stop = Some(request);
+                state.token.cancel();
+                None
+            } else if (state.status == DownloadStatus::Paused || state.status.is_failed()) && request == StopRequest::Cancel {
+                state.status = DownloadStatus::Cancelled;
+                Some(state.download.clone())
+            } else {
+                None
+            }
+        };
+
+        let Some(download) = stopped else {
+            return;
+        };
+
+        match request {
+            StopRequest::Pause => {
+                if let Err(e) = download.app.database().downloads().set_paused(track, true) {
+                    println!("Failed to pause {} : {}", download.track.title, e);
+                }
+            },
+            StopRequest::Cancel => discard(&download)
+        }
+    }
+
+    //paused downloads continue from their .part file, failed and cancelled ones start again, all with a new stream url
+    pub async fn resume(&self, track:&Track) -> Result<(), tidal_rs::error::Error> {
+        let (download, previous_status) = {
+            let mut download_state = self.download_state.lock().unwrap();
+            match download_state.get_mut(track) {
+                Some(state) if state.status.can_resume() => {
+                    let previous_status = std::mem::replace(&mut state.status, DownloadStatus::Queued);
+                    (state.download.clone(), previous_status)
+                },
+                _ => return Ok(())
+            }
+        };
+
+        let result = self.enqueue_track(download.app.clone(), download.quality, download.track.clone(), download.add_to_playlist.as_ref(), download.album.as_ref()).await;
+
+        //nothing was queued, the download can still be resumed or cancelled
+        if result.is_err() {
+            update_state(&self.download_state, track, |state| state.status = previous_status);
+        }
+
+        result
+    }
+
+    //in the order they were queued
+    fn tracks(&self) -> Vec<Track> {
+        let mut states = self.get_downloads();
+        states.sort_by_key(|state| state.started_at);
+        states.into_iter().map(|state| state.download.track).collect()
+    }
+
+    pub fn pause_all(&self) {
+        self.tracks().iter().for_each(|track| self.pause(track));
+    }
+
+    pub fn cancel_all(&self) {
+        self.tracks().iter().for_each(|track| self.cancel(track));
+    }
+
+    //cancelled and failed downloads are only started again one by one
+    pub async fn resume_all(&self) {
+        let paused = self.get_downloads().into_iter()
+            .filter(|state| state.status == DownloadStatus::Paused)
+            .map(|state| state.download.track)
+            .collect::<Vec<Track>>();
+
+        for track in self.tracks().into_iter().filter(|track| paused.contains(track)) {
+            if let Err(e) = self.resume(&track).await {
+                println!("Failed to resume the download of {} : {:?}", track.title, e);
+            }
+        }
+    }
+
+    //queued again with a new stream url, what was already written in the .part file is kept, paused downloads wait to be resumed
+    pub async fn restore_queue(&self, app:Arc<AppImpl>) {
+        let queue = {
+            app.database().downloads().get_queue()
+        };
+
+        for queued in queue {
+            let title = queued.track.title.clone();
+
+            let result = match queued.paused {
+                true => self.prepare(app.clone(), queued.quality, queued.track, queued.playlist.as_ref(), queued.album.as_ref()).await.map(|download| {
+                    let mut state = DownloadState::new(download.clone(), 0);
+                    state.status = DownloadStatus::Paused;
+                    self.download_state.lock().unwrap().insert(download.track, state);
+                }),
+                false => self.enqueue_track(app.clone(), queued.quality, queued.track, queued.playlist.as_ref(), queued.album.as_ref()).await
+            };
+
+            if let Err(e) = result {
+                println!("Failed to restore the download of {} : {:?}", title, e);
+            }
+        }
+    }
+
     pub fn get_queue(&self) -> VecDeque<Download>
     {
         self.download_queue.lock().unwrap().clone()
@@ -175,6 +499,15 @@ impl DownloadManager {
 
     pub fn remove_download(&self, download:Download) {
         self.download_queue.lock().unwrap().retain(|x| x.track != download.track);
+        let _ = download.app.database().downloads().remove(&download.track);
+    }
+
+    pub fn is_downloading(&self, path:&Path) -> bool {
+        if self.download_queue.lock().unwrap().iter().any(|download| download.path == path) {
+            return true;
+        }
+
+        self.download_state.lock().unwrap().values().any(|state| state.download.path == path && state.status.is_active())
     }
 
     pub fn downloaded_or_failed(&self, track:&Track) -> Option<Download> {
@@ -194,6 +527,20 @@ impl DownloadManager {
     }
 
     pub async fn enqueue_single(&self, app:Arc<AppImpl>, quality:AudioQuality, track:Track, add_to_playlist:Option<&Playlist>) -> Result<(), tidal_rs::error::Error>
+    {
+        self.enqueue_track(app, quality, track, add_to_playlist, None).await
+    }
+
+    async fn enqueue_track(&self, app:Arc<AppImpl>, quality:AudioQuality, track:Track, add_to_playlist:Option<&Playlist>, album:Option<&Album>) -> Result<(), tidal_rs::error::Error>
+    {
+        let download = self.prepare(app, quality, track, add_to_playlist, album).await?;
+        self.enqueue(download);
+
+        Ok(())
+    }
+
+    //asks for the stream url and picks where the file goes
+    async fn prepare(&self, app:Arc<AppImpl>, quality:AudioQuality, track:Track, add_to_playlist:Option<&Playlist>, album:Option<&Album>) -> Result<Download, tidal_rs::error::Error>
     {
         let caracteres_interdits = ['<', '>', ':', '"', '/', '\\', '|', '?', '*', '\'', '.'];
         let normalize_string = |x:String| -> String {
@@ -221,61 +568,43 @@ impl DownloadManager {
         let title = track.title.clone();
         let mime_type = manifest.mime_type.clone();
         let path: PathBuf = base_path.join(format!("{}.{}", normalize_string(title.replace("/", "-").replace("\\", "-")), mime_type.get_file_extension()));
-        let download = Download::new(app.clone(), track, manifest, Some(path), add_to_playlist.cloned());
-
-        self.enqueue(download);
-
-        Ok(())
+        Ok(Download::new(app.clone(), track, manifest, Some(path), quality, add_to_playlist.cloned(), album.cloned()))
     }
 
+    //the album is saved with each of its tracks, it is added when the last one is done even after a restart
     pub async fn enqueue_album(&self, app:Arc<AppImpl>, album:Album, quality:AudioQuality) -> Result<(), tidal_rs::error::Error>
     {
         let tracks = app.tidal_client.media().get_album_tracks(album.id, None).await.unwrap_or(vec![]);
 
-        for track in tracks.clone() {
-            self.enqueue_single(app.clone(), quality, track, None).await?;
+        //the artist page sorts the album by this count
+        if !tracks.is_empty() {
+            if let Err(e) = app.database().albums().set_track_count(&album, tracks.len()) {
+                println!("Failed to save album track count : {}", e);
+            }
         }
 
-        let mut handles = vec![];
-
-
         for track in tracks {
-            let app = app.clone();
-            handles.push(
-                task::spawn(async move {
-                    loop {
-                        if let Some(result) = app.download_manager.downloaded_or_failed(&track) {
-                            return result;
-                        } else {
-                            tokio::time::sleep(Duration::from_millis(50)).await;
-                        }
-                    }
-                })
-            )
+            self.enqueue_track(app.clone(), quality, track, None, Some(&album)).await?;
         }
 
-
-        let tracks = join_all(handles).await.iter()
-            .filter_map(|x| x.as_ref().ok())
-            .map(|x| Song::new_with_track(x.path.clone(), x.track.clone()))
-            .collect::<Vec<_>>();
-
-        app.database().albums().add_album(&album, tracks);
-     
-
         Ok(())
     }
 
     pub fn work(&self) {
         let queue = Arc::clone(&self.download_queue);
         let download_state = Arc::clone(&self.download_state);
+        let max_retries = Arc::clone(&self.max_retries);
 
         for _ in 0..self.max_concurrency {
             let queue = Arc::clone(&queue);
             let download_state = Arc::clone(&download_state);
+            let max_retries = Arc::clone(&max_retries);
 
             task::spawn(async move {
-                let client = reqwest::Client::new();
+                let client = reqwest::Client::builder()
+                    .connect_timeout(CONNECT_TIMEOUT)
+                    .build()
+                    .unwrap_or_default();
                 loop {
                     let download = {
                         let mut queue = queue.lock().unwrap();
@@ -283,72 +612,90 @@ impl DownloadManager {
                     };
 
                     match download {
-                        Some(download) => {
-                            let url = &download.manifest.urls[0];
-                            
-                            if let Ok(mut response) = client.get(url).send().await {
-                                let total_size = response.content_length().unwrap_or(0) as usize;
-                                let mut downloaded = 0;
-                                let mut on_last_second_downloaded:(usize, Instant) = (0, Instant::now());
-                                let folder = download.path.parent().unwrap();
-                                if !folder.exists() {
-                                    std::fs::create_dir_all(folder).unwrap();
-                                }
-
-                                let state = DownloadState::new(download.clone(), total_size);
-                                {
-                                    let mut download_state = download_state.lock().unwrap();
-                                    download_state.insert(state.download.track.clone(), state);
-                                }
-
-                                let file_result = tokio::fs::File::create(download.path.clone()).await;
-
-                                if file_result.is_err() {
-                                    let mut download_state = download_state.lock().unwrap();
-                                    let state = download_state.get_mut(&download.track).unwrap();
-                                    dbg!(download.path.clone());
-                                    state.status = DownloadStatus::Failed(file_result.unwrap_err().to_string());
-                                    continue;
-                                }
-
-                                let mut file = file_result.unwrap();
-       
-
-                                while let Some(chunk) = response.chunk().await.unwrap() {
-                                    downloaded += chunk.len() as u64;
-                                    on_last_second_downloaded.0 += chunk.len();
-
-                                    file.write_all(&chunk).await.unwrap();
-
-                                    //calculate speed, eta and progress, then update the state
-                                    {
-                                        let mut download_state = download_state.lock().unwrap();
-                                        let state = download_state.get_mut(&download.track).unwrap();
+                        Some(mut download) => {
+                            //the token of the queued state is kept, a pause asked in between isn't lost
+                            let token = {
+                                let mut download_state = download_state.lock().unwrap();
+                                let (token, stop) = download_state.get(&download.track)
+                                    .map(|state| (state.token.clone(), state.stop))
+                                    .unwrap_or_default();
+
+                                let mut state = DownloadState::new(download.clone(), 0);
+                                state.token = token.clone();
+                                state.stop = stop;
+                                download_state.insert(download.track.clone(), state);
+
+                                token
+                            };
+
+                            let mut attempt = 0;
+
+                            //every attempt resumes from the .part file left by the previous one
+                            let result = loop {
+                                let result = tokio::select! {
+                                    result = try_download(&client, &download, &download_state) => result,
+                                    _ = token.cancelled() => Err(DownloadError::Stopped)
+                                };
+
+                                match result {
+                                    Ok(lossless) => break Ok(lossless),
+                                    Err(error) if error.is_retryable() && attempt < max_retries.load(Ordering::Relaxed) => {
+                                        attempt += 1;
+
+                                        if error.is_expired_url() {
+                                            match download.app.tidal_client.media().get_highest_quality_avaliable_stream_url(download.track.id, download.quality).await {
+                                                Ok(manifest) => download.manifest = manifest,
+                                                Err(_) => break Err(error)
+                                            }
+                                        }
 
-                                        state.downloaded = downloaded as usize;
-                                      
-                                        //calculer la vitesse
-                                        let _elapsed = state.started_at.elapsed();
-                                        //bytes per second
-                                        state.speed = DataRate::new(on_last_second_downloaded.0 as f32 / on_last_second_downloaded.1.elapsed().as_secs_f32());
-                                        state.progress = downloaded as f32 / total_size as f32;
-                                        state.status = DownloadStatus::Downloading;    
+                                        update_state(&download_state, &download.track, |state| state.status = DownloadStatus::Retrying(attempt, error));
 
-                                        if on_last_second_downloaded.1.elapsed().as_secs() >= 1 {
-                                            on_last_second_downloaded.1 = Instant::now();
-                                            on_last_second_downloaded.0 = 0;
+                                        tokio::select! {
+                                            _ = tokio::time::sleep(backoff(attempt)) => (),
+                                            _ = token.cancelled() => break Err(DownloadError::Stopped)
                                         }
-                                    };
+                                    },
+                                    Err(error) => break Err(error)
+                                }
+                            };
+
+                            let stop = {
+                                download_state.lock().unwrap().get(&download.track).and_then(|state| state.stop)
+                            };
+
+                            //a paused download stays in the saved queue and keeps its .part file
+                            if result == Err(DownloadError::Stopped) {
+                                match stop {
+                                    Some(StopRequest::Pause) => {
+                                        let _ = download.app.database().downloads().set_paused(&download.track, true);
+                                        update_state(&download_state, &download.track, |state| state.status = DownloadStatus::Paused);
+                                    },
+                                    _ => {
+                                        discard(&download);
+                                        update_state(&download_state, &download.track, |state| state.status = DownloadStatus::Cancelled);
+                                    }
                                 }
 
-                                {
-                                    let mut download_state = download_state.lock().unwrap();
-                                    let state = download_state.get_mut(&download.track).unwrap();
+                                continue;
+                            }
 
-                                    state.status = DownloadStatus::Finished;
-                                    download.on_finished();
+                            match result {
+                                Ok(lossless) => {
+                                    download.on_finished(lossless);
+
+                                    //after the song is added, the album may be complete with it
+                                    let _ = download.app.database().downloads().finish(&download.track);
+                                    update_state(&download_state, &download.track, |state| state.status = DownloadStatus::Finished);
+                                },
+                                Err(error) => {
+                                    println!("Failed to download {} : {}", download.track.title, error);
+                                    let _ = download.app.database().downloads().remove(&download.track);
+                                    update_state(&download_state, &download.track, |state| state.status = DownloadStatus::Failed(error));
                                 }
                             }
+
+                            download.complete_album();
                         }
                         None => (),
                     }
@@ -358,4 +705,107 @@ impl DownloadManager {
             });
         }
     }
-}
\ No newline at end of file
+}
+
+//a cancelled download leaves nothing behind, neither its .part file nor its place in the saved queue
+fn discard(download:&Download) {
+    if let Err(e) = std::fs::remove_file(part_path(&download.path)) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            println!("Failed to delete {} : {}", part_path(&download.path).display(), e);
+        }
+    }
+
+    let _ = download.app.database().downloads().remove(&download.track);
+    download.complete_album();
+}
+
+fn update_state(download_state:&Mutex<HashMap<Track, DownloadState>>, track:&Track, update:impl FnOnce(&mut DownloadState)) {
+    if let Some(state) = download_state.lock().unwrap().get_mut(track) {
+        update(state);
+    }
+}
+
+//1s, 2s, 4s... between the attempts, never more than a minute
+fn backoff(attempt:usize) -> Duration {
+    let delay = RETRY_BASE_DELAY.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1) as u32));
+    delay.min(RETRY_MAX_DELAY)
+}
+
+async fn try_download(client:&reqwest::Client, download:&Download, download_state:&Mutex<HashMap<Track, DownloadState>>) -> Result<Option<bool>, DownloadError> {
+    let url = download.manifest.urls.first().ok_or(DownloadError::MissingUrl)?;
+    let part_path = part_path(&download.path);
+
+    if let Some(folder) = download.path.parent() {
+        tokio::fs::create_dir_all(folder).await?;
+    }
+
+    let (mut response, mut file, mut downloaded) = tokio::time::timeout(READ_TIMEOUT, open_part(client, url, &part_path)).await
+        .map_err(|_| DownloadError::Request("the server didn't answer".to_string()))??;
+
+    let total_size = downloaded + response.content_length().unwrap_or(0) as usize;
+    let mut on_last_second_downloaded:(usize, Instant) = (0, Instant::now());
+
+    update_state(download_state, &download.track, |state| {
+        state.total_size = total_size;
+        state.downloaded = downloaded;
+        state.status = DownloadStatus::Downloading;
+    });
+
+    loop {
+        let chunk = tokio::time::timeout(READ_TIMEOUT, response.chunk()).await
+            .map_err(|_| DownloadError::Request(format!("no data received for {} seconds", READ_TIMEOUT.as_secs())))??;
+
+        let Some(chunk) = chunk else {
+            break;
+        };
+
+        file.write_all(&chunk).await?;
+
+        downloaded += chunk.len();
+        on_last_second_downloaded.0 += chunk.len();
+
+        //calculate speed, eta and progress, then update the state
+        update_state(download_state, &download.track, |state| {
+            state.downloaded = downloaded;
+
+            //bytes per second
+            state.speed = DataRate::new(on_last_second_downloaded.0 as f32 / on_last_second_downloaded.1.elapsed().as_secs_f32());
+            state.progress = downloaded as f32 / total_size as f32;
+        });
+
+        if on_last_second_downloaded.1.elapsed().as_secs() >= 1 {
+            on_last_second_downloaded.1 = Instant::now();
+            on_last_second_downloaded.0 = 0;
+        }
+    }
+
+    file.flush().await?;
+    drop(file);
+
+    if downloaded < total_size {
+        return Err(DownloadError::Incomplete { received: downloaded, expected: total_size });
+    }
+
+    //the file is playable without its tags, a failure here doesn't fail the download
+    let cover = tagging::download_cover(&download.track).await;
+    let tagged_path = part_path.clone();
+    let track = download.track.clone();
+
+    //the codec tidal sent is read back from the file, a lossless quality may still give aac
+    let lossless = match task::spawn_blocking(move || (tagging::write_tags(&tagged_path, &track, cover), tagging::read_lossless(&tagged_path).ok())).await {
+        Ok((Err(e), lossless)) => {
+            println!("Failed to tag {} : {}", part_path.display(), e);
+            lossless
+        },
+        Err(e) => {
+            println!("Failed to tag {} : {}", part_path.display(), e);
+            None
+        },
+        Ok((Ok(()), lossless)) => lossless
+    };
+
+    //tagged before the rename, a file with the final name is always complete
+    tokio::fs::rename(&part_path, &download.path).await?;
+
+    Ok(lossless)
+}
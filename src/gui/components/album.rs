@@ -3,7 +3,7 @@ use std::sync::Arc;
 use egui::{pos2, vec2, Align2, Color32, FontFamily, FontId, Image, Label, RichText, Rounding};
 use tidal_rs::model::Album;
 
-use crate::{app, constants::{BACKGROUND_COLOR, TEXT_COLOR}, gui::{helper, model::Pages, page::RenderablePage, song::SongWidget}, renderer::Drawable};
+use crate::{app, constants::{BACKGROUND_COLOR, SELECTION_COLOR, TEXT_COLOR}, gui::{helper, model::Pages, page::RenderablePage, song::SongWidget}, renderer::Drawable, undo::{self, Command}};
 
 
 
@@ -34,6 +34,16 @@ impl RenderablePage for Album {
                     application.gui_settings.location = crate::gui::model::UserLocation::Home;
                     application.gui_settings.page = Pages::Home;
                 }
+
+                //the songs stay in the library, only the album page goes away
+                if ui.button("Remove album").clicked() {
+                    if let Err(e) = undo::execute(&application.app, Command::remove_album(self)) {
+                        println!("Failed to remove album : {}", e);
+                    }
+
+                    application.gui_settings.location = crate::gui::model::UserLocation::Home;
+                    application.gui_settings.page = Pages::Home;
+                }
             }).response
         }).inner;
 
@@ -52,11 +62,22 @@ impl RenderablePage for Album {
         let mut container = ui.child_ui(list_rect, egui::Layout::default());
 
         egui::ScrollArea::new([false, true]).show(&mut container, |container: &mut egui::Ui| {
-            tracks.iter().for_each(|song| {
-                let response = container.add(SongWidget::new(song.clone()));
+            let list = format!("album:{}", self.id);
+
+            tracks.iter().enumerate().for_each(|(index, song)| {
+                let widget = if application.gui_settings.selection.is_selected(&list, song) {
+                    SongWidget::new(song.clone()).background_color(SELECTION_COLOR)
+                } else {
+                    SongWidget::new(song.clone())
+                };
+
+                let response = container.add(widget);
 
                 if response.clicked() {
-                    song.on_clicked(application.app.clone(), crate::gui::model::UserLocation::Album(self.clone()));
+                    let modifiers = container.input(|input| input.modifiers);
+                    if !application.gui_settings.selection.handle_click(&list, &tracks, index, modifiers) {
+                        song.on_clicked(application.app.clone(), crate::gui::model::UserLocation::Album(self.clone()));
+                    }
                 }
 
                 song.context_menu(response, ui, application);
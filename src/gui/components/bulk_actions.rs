@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use tidal_rs::model::AudioQuality;
+
+use crate::{app::{App, AppImpl}, gui::model::{Pages, UserLocation}, playlist::{DecodedPlaylist, Playlist, PlaylistDescriptor}, playlist_file::{self, PlaylistFormat}, song::Song, undo::{self, Command}};
+
+//returns true when the songs were added to a playlist
+pub fn add_to_playlist_menu(ui:&mut egui::Ui, app:&Arc<AppImpl>, songs:&Vec<Song>) -> bool {
+    let mut added = false;
+
+    ui.menu_button("Add to playlist", |ui| {
+        let playlists = {
+            app.database().playlists().get_playlists()
+        };
+
+        //smart playlists are filled by their rules
+        for playlist in playlists.iter().filter(|playlist| !playlist.smart) {
+            if ui.button(&playlist.name).clicked() {
+                if let Err(e) = undo::execute(app, Command::add_to_playlist(playlist, songs.clone())) {
+                    println!("Failed to add to playlist : {}", e);
+                }
+                added = true;
+                ui.close_menu();
+            }
+        }
+    });
+
+    added
+}
+
+impl App {
+    //context menu of a song when several songs are selected, every action applies to all of them
+    pub fn bulk_actions_menu(&mut self, ui:&mut egui::Ui, songs:Vec<Song>) {
+        let mut done = false;
+
+        ui.label(format!("{} songs selected", songs.len()));
+        ui.separator();
+
+        done |= add_to_playlist_menu(ui, &self.app, &songs);
+
+        if ui.button("Add to queue").clicked() {
+            let mut queue = self.app.player.queue();
+            songs.iter().for_each(|song| queue.add_to_queue(song));
+            done = true;
+        }
+
+        if self.gui_settings.page == Pages::Playlist {
+            if let UserLocation::Playlist(playlist) = &self.gui_settings.location {
+                if !playlist.smart && ui.button("Remove from playlist").clicked() {
+                    if let Err(e) = undo::execute(&self.app, Command::remove_from_playlist(playlist, songs.clone())) {
+                        println!("Failed to remove from playlist : {}", e);
+                    }
+                    done = true;
+                }
+            }
+        }
+
+        if ui.button("Delete files").on_hover_text("The files are moved to the trash and can be restored from the settings").clicked() {
+            if let Err(e) = undo::execute(&self.app, Command::trash_songs(songs.clone())) {
+                println!("Failed to trash songs : {}", e);
+            }
+            done = true;
+        }
+
+        let tracks = songs.iter().filter_map(|song| song.tidal_track.clone()).collect::<Vec<_>>();
+
+        if !tracks.is_empty() && ui.button("Download again in the highest quality").clicked() {
+            let app = self.app.clone();
+
+            tokio::spawn(async move {
+                let quality = app.tidal_client.user().get_current_account_highest_sound_quality().await.unwrap_or(AudioQuality::Lossless);
+
+                for track in tracks {
+                    let _ = app.download_manager.enqueue_single(app.clone(), quality, track, None).await;
+                }
+            });
+            done = true;
+        }
+
+        ui.menu_button("Export", |ui| {
+            ui.checkbox(&mut self.gui_settings.export_relative_paths, "Relative paths");
+
+            for format in PlaylistFormat::ALL {
+                if ui.button(format.to_string()).clicked() {
+                    let destination = rfd::FileDialog::new()
+                        .set_file_name(format!("Selection.{}", format.extension()))
+                        .add_filter(format.to_string(), &[format.extension()])
+                        .save_file();
+
+                    if let Some(destination) = destination {
+                        let selection = DecodedPlaylist {
+                            descriptor: PlaylistDescriptor::from(Playlist {
+                                id: Playlist::generate_id(),
+                                name: "Selection".to_string(),
+                                image: None,
+                                description: None,
+                                songs: vec![],
+                                rules: None
+                            }),
+                            songs: songs.clone()
+                        };
+
+                        if let Err(e) = playlist_file::export(&selection, &destination, format, self.gui_settings.export_relative_paths) {
+                            println!("Failed to export selection : {}", e);
+                        }
+                    }
+
+                    done = true;
+                }
+            }
+        });
+
+        if ui.button("Clear selection").clicked() {
+            done = true;
+        }
+
+        if done {
+            self.gui_settings.selection.clear();
+            ui.close_menu();
+        }
+    }
+}
@@ -1 +1,5 @@
-pub mod album;
\ No newline at end of file
+pub mod album;
+pub mod rules_editor;
+pub mod playlist_tree;
+pub mod bulk_actions;
+pub mod undo_toast;
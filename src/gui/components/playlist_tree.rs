@@ -0,0 +1,243 @@
+use egui::{Button, CollapsingHeader, Id, Rect, RichText, ScrollArea, Sense, Window};
+
+use crate::{app::App, gui::model::{Pages, UserLocation}, playlist::{PlaylistDescriptor, PlaylistFolder}};
+
+//what is being dragged in the tree, kept in memory until the pointer is released
+#[derive(Clone)]
+enum DraggedItem {
+    Playlist(PlaylistDescriptor),
+    Folder(PlaylistFolder)
+}
+
+impl DraggedItem {
+    fn name(&self) -> &str {
+        match self {
+            DraggedItem::Playlist(playlist) => &playlist.name,
+            DraggedItem::Folder(folder) => &folder.name
+        }
+    }
+}
+
+fn dragged_item_id() -> Id {
+    Id::new("_dragged_playlist_item_")
+}
+
+//folders a playlist or a folder can be moved to from its context menu
+fn move_to_menu(ui:&mut egui::Ui, folders:&Vec<PlaylistFolder>, current:&Option<String>, excluded:Option<&str>) -> Option<Option<String>> {
+    let mut target = None;
+
+    ui.menu_button("Move to folder", |ui| {
+        if current.is_some() && ui.button("Root").clicked() {
+            target = Some(None);
+            ui.close_menu();
+        }
+
+        for folder in folders {
+            if Some(folder.id.as_str()) == excluded || current.as_ref() == Some(&folder.id) {
+                continue;
+            }
+
+            if ui.button(&folder.name).clicked() {
+                target = Some(Some(folder.id.clone()));
+                ui.close_menu();
+            }
+        }
+    });
+
+    target
+}
+
+impl App {
+    pub fn draw_playlist_tree(&mut self, ui:&mut egui::Ui) {
+        let new_folder = Id::new("_new_playlist_folder");
+        let rename_folder = Id::new("_rename_playlist_folder");
+
+        let (folders, playlists) = {
+            let database = self.app.database();
+            (database.playlists().get_folders(), database.playlists().get_playlists())
+        };
+
+        if ui.memory(|mem| mem.data.get_temp(new_folder).unwrap_or(false)) == true {
+            Window::new("New folder").show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Name").strong());
+                    ui.text_edit_singleline(&mut self.gui_settings.new_folder_name);
+                });
+                ui.horizontal(|ui| {
+                    if !self.gui_settings.new_folder_name.is_empty() && ui.button("Create").clicked() {
+                        if let Err(e) = self.app.database().playlists().add_folder(&self.gui_settings.new_folder_name, self.gui_settings.new_folder_parent.as_deref()) {
+                            println!("Failed to create folder : {}", e);
+                        }
+                        self.gui_settings.new_folder_name.clear();
+
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(new_folder));
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(new_folder));
+                    }
+                });
+            });
+        }
+
+        if let Some(folder) = self.gui_settings.renamed_folder.clone() {
+            Window::new("Rename folder").id(rename_folder).show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Name").strong());
+                    ui.text_edit_singleline(&mut self.gui_settings.folder_name_edit);
+                });
+                ui.horizontal(|ui| {
+                    if !self.gui_settings.folder_name_edit.is_empty() && ui.button("Save").clicked() {
+                        if let Err(e) = self.app.database().playlists().rename_folder(&folder, &self.gui_settings.folder_name_edit) {
+                            println!("Failed to rename folder : {}", e);
+                        }
+                        self.gui_settings.renamed_folder = None;
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        self.gui_settings.renamed_folder = None;
+                    }
+                });
+            });
+        }
+
+        //rects the dragged item can be dropped on, the innermost folder is pushed last
+        let mut drop_zones:Vec<(Rect, Option<String>)> = vec![];
+
+        let root = ui.label(RichText::new("Playlists").strong());
+        drop_zones.push((root.rect, None));
+
+        ui.horizontal(|ui| {
+            if ui.small_button("New folder").clicked() {
+                self.gui_settings.new_folder_parent = None;
+                ui.memory_mut(|mem| mem.data.insert_temp(new_folder, true));
+            }
+        });
+
+        ScrollArea::new([false, true]).id_source("_playlists_").show(ui, |ui| {
+            self.draw_folder_content(ui, None, &folders, &playlists, &mut drop_zones);
+        });
+
+        let dragged = ui.memory(|mem| mem.data.get_temp::<DraggedItem>(dragged_item_id()));
+
+        if let Some(dragged) = dragged {
+            egui::show_tooltip_at_pointer(ui.ctx(), Id::new("_dragged_playlist_tooltip_"), |ui| {
+                ui.label(dragged.name());
+            });
+
+            if ui.input(|input| input.pointer.any_released()) {
+                let pointer = ui.input(|input| input.pointer.interact_pos());
+                let target = pointer.and_then(|pointer| drop_zones.iter().rev().find(|(rect, _)| rect.contains(pointer)));
+
+                if let Some((_, folder)) = target {
+                    match &dragged {
+                        DraggedItem::Playlist(playlist) => {
+                            if let Err(e) = self.app.database().playlists().move_playlist_to_folder(playlist, folder.as_deref()) {
+                                println!("Failed to move playlist : {}", e);
+                            }
+                        },
+                        DraggedItem::Folder(dragged_folder) => {
+                            if let Err(e) = self.app.database().playlists().move_folder(dragged_folder, folder.as_deref()) {
+                                println!("Failed to move folder : {}", e);
+                            }
+                        }
+                    }
+                }
+
+                ui.memory_mut(|mem| mem.data.remove::<DraggedItem>(dragged_item_id()));
+            }
+        }
+    }
+
+    fn draw_folder_content(&mut self, ui:&mut egui::Ui, parent:Option<&str>, folders:&Vec<PlaylistFolder>, playlists:&Vec<PlaylistDescriptor>, drop_zones:&mut Vec<(Rect, Option<String>)>) {
+        for folder in folders.iter().filter(|folder| folder.parent.as_deref() == parent) {
+            let response = CollapsingHeader::new(&folder.name)
+                .id_source(&folder.id)
+                .open(Some(folder.expanded))
+                .show(ui, |ui| {
+                    self.draw_folder_content(ui, Some(&folder.id), folders, playlists, drop_zones);
+                });
+
+            let header = response.header_response.interact(Sense::click_and_drag());
+            drop_zones.push((header.rect, Some(folder.id.clone())));
+
+            if header.clicked() {
+                if let Err(e) = self.app.database().playlists().set_folder_expanded(folder, !folder.expanded) {
+                    println!("Failed to save folder state : {}", e);
+                }
+            }
+
+            if header.drag_started() {
+                ui.memory_mut(|mem| mem.data.insert_temp(dragged_item_id(), DraggedItem::Folder(folder.clone())));
+            }
+
+            header.context_menu(|ui| {
+                if ui.button("Play all").clicked() {
+                    let songs = {
+                        self.app.database().playlists().get_folder_songs(folder)
+                    };
+
+                    if let Some(first) = songs.first() {
+                        let _ = self.app.player.set_media(first);
+
+                        let mut queue = self.app.player.queue();
+                        queue.set_playlist(&songs);
+                        queue.current_index = Some(0);
+                    }
+
+                    ui.close_menu();
+                }
+
+                if ui.button("New subfolder").clicked() {
+                    self.gui_settings.new_folder_parent = Some(folder.id.clone());
+                    ui.memory_mut(|mem| mem.data.insert_temp(Id::new("_new_playlist_folder"), true));
+                    ui.close_menu();
+                }
+
+                if ui.button("Rename").clicked() {
+                    self.gui_settings.folder_name_edit = folder.name.clone();
+                    self.gui_settings.renamed_folder = Some(folder.clone());
+                    ui.close_menu();
+                }
+
+                if let Some(target) = move_to_menu(ui, folders, &folder.parent, Some(&folder.id)) {
+                    if let Err(e) = self.app.database().playlists().move_folder(folder, target.as_deref()) {
+                        println!("Failed to move folder : {}", e);
+                    }
+                }
+
+                //the playlists inside go back to the root, they are not deleted
+                if ui.button("Delete folder").clicked() {
+                    if let Err(e) = self.app.database().playlists().remove_folder(folder) {
+                        println!("Failed to remove folder : {}", e);
+                    }
+                    ui.close_menu();
+                }
+            });
+        }
+
+        for playlist in playlists.iter().filter(|playlist| playlist.folder.as_deref() == parent) {
+            let response = ui.add(Button::new(playlist.name.clone()).sense(Sense::click_and_drag()));
+
+            if response.clicked() {
+                self.gui_settings.location = UserLocation::Playlist(
+                    playlist.clone()
+                );
+
+                self.gui_settings.page = Pages::Playlist;
+            }
+
+            if response.drag_started() {
+                ui.memory_mut(|mem| mem.data.insert_temp(dragged_item_id(), DraggedItem::Playlist(playlist.clone())));
+            }
+
+            response.context_menu(|ui| {
+                if let Some(target) = move_to_menu(ui, folders, &playlist.folder, None) {
+                    if let Err(e) = self.app.database().playlists().move_playlist_to_folder(playlist, target.as_deref()) {
+                        println!("Failed to move playlist : {}", e);
+                    }
+                }
+            });
+        }
+    }
+}
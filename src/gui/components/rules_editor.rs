@@ -0,0 +1,141 @@
+use egui::{ComboBox, DragValue};
+
+use crate::{smart_playlist::{Rule, SmartRules, TextField, TextMatch}, time};
+
+fn optional_value(ui:&mut egui::Ui, label:&str, value:&mut Option<u64>, default:u64, suffix:&str) -> bool {
+    let mut enabled = value.is_some();
+    let mut changed = ui.checkbox(&mut enabled, label).changed();
+
+    if changed {
+        *value = if enabled { Some(default) } else { None };
+    }
+
+    if let Some(value) = value {
+        changed |= ui.add(DragValue::new(value).suffix(suffix)).changed();
+    }
+
+    changed
+}
+
+fn optional_year(ui:&mut egui::Ui, label:&str, value:&mut Option<u64>, offset:i64) -> bool {
+    let current_year = time::year_of(time::now());
+
+    let mut enabled = value.is_some();
+    let mut changed = ui.checkbox(&mut enabled, label).changed();
+
+    if changed {
+        *value = if enabled { Some(time::year_start(current_year + offset)) } else { None };
+    }
+
+    if let Some(secs) = value {
+        //"to" is the first second of the next year so the year shown is the one before
+        let mut year = time::year_of(*secs) - offset;
+        if ui.add(DragValue::new(&mut year).clamp_range(1970..=current_year + 1)).changed() {
+            *secs = time::year_start(year + offset);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+fn rule_editor(ui:&mut egui::Ui, id_source:usize, rule:&mut Rule) -> bool {
+    let mut changed = false;
+
+    ComboBox::from_id_source(("_rule_kind_", id_source)).selected_text(rule.name()).show_ui(ui, |ui| {
+        for default in Rule::defaults() {
+            let name = default.name();
+            if ui.selectable_label(rule.name() == name, name).clicked() && rule.name() != name {
+                *rule = default;
+                changed = true;
+            }
+        }
+    });
+
+    match rule {
+        Rule::Text { field, matching, value } => {
+            ComboBox::from_id_source(("_rule_field_", id_source)).selected_text(field.to_string()).show_ui(ui, |ui| {
+                for option in [TextField::Title, TextField::Artist, TextField::Album] {
+                    changed |= ui.selectable_value(field, option, option.to_string()).changed();
+                }
+            });
+
+            ComboBox::from_id_source(("_rule_matching_", id_source)).selected_text(matching.to_string()).show_ui(ui, |ui| {
+                for option in [TextMatch::Contains, TextMatch::Is, TextMatch::IsNot] {
+                    changed |= ui.selectable_value(matching, option, option.to_string()).changed();
+                }
+            });
+
+            changed |= ui.text_edit_singleline(value).changed();
+        },
+        Rule::AddedBetween { from, to } => {
+            changed |= optional_year(ui, "from", from, 0);
+            changed |= optional_year(ui, "to", to, 1);
+        },
+        Rule::PlayCount { min, max } => {
+            changed |= optional_value(ui, "at least", min, 1, "");
+            changed |= optional_value(ui, "at most", max, 0, "");
+        },
+        Rule::Lossless(lossless) => {
+            changed |= ui.checkbox(lossless, "is lossless").changed();
+        },
+        Rule::Duration { min, max } => {
+            //edited in seconds, stored in milliseconds
+            let mut min_secs = min.map(|min| min / 1000);
+            let mut max_secs = max.map(|max| max / 1000);
+
+            if optional_value(ui, "at least", &mut min_secs, 60, "s") | optional_value(ui, "at most", &mut max_secs, 300, "s") {
+                *min = min_secs.map(|min| min * 1000);
+                *max = max_secs.map(|max| max * 1000);
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+//returns true when the rules were modified
+pub fn rules_editor(ui:&mut egui::Ui, rules:&mut SmartRules) -> bool {
+    let mut changed = false;
+
+    ui.horizontal(|ui| {
+        ui.label("Songs matching");
+        ComboBox::from_id_source("_rules_match_all_").selected_text(if rules.match_all { "all rules" } else { "any rule" }).show_ui(ui, |ui| {
+            changed |= ui.selectable_value(&mut rules.match_all, true, "all rules").changed();
+            changed |= ui.selectable_value(&mut rules.match_all, false, "any rule").changed();
+        });
+    });
+
+    let mut removed = None;
+
+    for (index, rule) in rules.rules.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= rule_editor(ui, index, rule);
+
+            if ui.button("Remove").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+
+    if let Some(index) = removed {
+        rules.rules.remove(index);
+        changed = true;
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add rule").clicked() {
+            rules.rules.push(Rule::defaults().remove(0));
+            changed = true;
+        }
+
+        let mut limit = rules.limit.map(|limit| limit as u64);
+        if optional_value(ui, "limit to", &mut limit, 25, " songs") {
+            rules.limit = limit.map(|limit| limit as usize);
+            changed = true;
+        }
+    });
+
+    changed
+}
@@ -0,0 +1,56 @@
+use egui::{vec2, Align2, RichText};
+
+use crate::{app::App, constants::TEXT_COLOR, undo};
+
+impl App {
+    //ctrl+z and ctrl+shift+z, left to the text fields while one of them is focused
+    pub fn handle_undo_shortcuts(&mut self, ctx:&egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        //shift is checked first, ctrl+z would also match ctrl+shift+z
+        if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)) {
+            if let Err(e) = undo::redo(&self.app) {
+                println!("Failed to redo : {}", e);
+            }
+        } else if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::Z)) {
+            if let Err(e) = undo::undo(&self.app) {
+                println!("Failed to undo : {}", e);
+            }
+        }
+    }
+
+    pub fn draw_undo_toast(&mut self, ctx:&egui::Context) {
+        let toast = {
+            self.app.undo_stack.lock().unwrap().toast()
+        };
+
+        let Some((message, remaining)) = toast else {
+            return;
+        };
+
+        egui::Area::new("_undo_toast")
+            .anchor(Align2::CENTER_BOTTOM, vec2(0., -130.))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(message).color(TEXT_COLOR));
+
+                        if ui.button("Undo").clicked() {
+                            if let Err(e) = undo::undo(&self.app) {
+                                println!("Failed to undo : {}", e);
+                            }
+                        }
+
+                        if ui.small_button("✖").clicked() {
+                            self.app.undo_stack.lock().unwrap().dismiss_toast();
+                        }
+                    });
+                });
+            });
+
+        //hidden on time even if nothing else happens
+        ctx.request_repaint_after(remaining);
+    }
+}
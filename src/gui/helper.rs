@@ -2,7 +2,7 @@ use std::ops::RangeInclusive;
 use std::hash::Hash;
 use egui::{emath, Rect, Response, Color32, Ui, Label, RichText, Vec2, FontId, Pos2, InnerResponse, Id, Sense};
 
-use crate::constants::{SECONDARY_HOVER_COLOR, TEXT_COLOR};
+use crate::{constants::{SECONDARY_HOVER_COLOR, TEXT_COLOR}, search_index};
 
 pub fn place_text_at(ui:&mut egui::Ui, font:FontId, pos:Pos2, text:String, is_max:bool) {
     let size = ui.painter().layout_no_wrap(text.clone(), font.clone(), TEXT_COLOR).size();
@@ -99,7 +99,5 @@ pub fn croix(ui:&mut egui::Ui, center:Pos2) {
 }
 //cette fonction sert a simplifier le titre d'une chanson / le nom d'un album / le nom d'un artiste pour pouvoir simplifier la recherche d'album
 pub fn simplify(title: &str) -> String {
-  let lowercase_title = title.to_lowercase();
-
-  lowercase_title
+  search_index::fold(title)
 }
\ No newline at end of file
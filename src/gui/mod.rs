@@ -5,4 +5,5 @@ pub mod helper;
 pub mod song;
 pub mod page;
 pub mod add_button_animated;
-pub mod components;
\ No newline at end of file
+pub mod components;
+pub mod selection;
\ No newline at end of file
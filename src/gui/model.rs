@@ -1,18 +1,19 @@
 use std::{time::Instant};
 
 
-use tidal_rs::model::{ Album, Artist, DeviceAuth, SearchResult, SearchType };
+use tidal_rs::model::{ Album, DeviceAuth, SearchResult, SearchType };
 
 
-use crate::{playlist::{DecodedPlaylist, Playlist, PlaylistDescriptor}, song::Song};
-use super::page::RenderablePage;
+use crate::{artist::LocalArtist, backup::BackupEntry, playlist::{DecodedPlaylist, Playlist, PlaylistDescriptor, PlaylistFolder}, playlist_file::ImportReport, smart_playlist::SmartRules, song::Song};
+use super::{page::RenderablePage, pages::library::LibraryView, selection::SongSelection};
 
 #[derive(PartialEq)]
 pub enum Event {
     SearchResult(SearchResult),
     SongArray(Vec<Song>),
     DeviceCode(Option<DeviceAuth>),
-    LogonWithTidal
+    LogonWithTidal,
+    PlaylistImported(ImportReport)
 }
 #[derive(PartialEq)]
 pub enum Pages {
@@ -21,6 +22,7 @@ pub enum Pages {
     Artist,
     Album,
     Search,
+    Library,
     Downloads,
     Settings
 }
@@ -31,7 +33,7 @@ type EventManager = (tokio::sync::mpsc::Sender<Event>, tokio::sync::mpsc::Receiv
 pub enum UserLocation {
     Home,
     Playlist(PlaylistDescriptor),
-    Artist(Artist),
+    Artist(LocalArtist),
     Album(Album)
 }
 
@@ -50,7 +52,21 @@ pub struct GuiInput {
     pub device_code:Option<DeviceAuth>,
     pub new_playlist_name:String,
     pub song_name_to_add:String,
-    pub add_songs:Vec<Song>
+    pub add_songs:Vec<Song>,
+    pub new_smart_rules:SmartRules,
+    pub playlist_import_report:Option<ImportReport>,
+    pub export_relative_paths:bool,
+    pub playlist_name_edit:String,
+    pub playlist_description_edit:String,
+    pub new_folder_name:String,
+    pub new_folder_parent:Option<String>,
+    pub renamed_folder:Option<PlaylistFolder>,
+    pub folder_name_edit:String,
+    pub library_view:LibraryView,
+    pub selection:SongSelection,
+    pub recovery_messages:Vec<String>,
+    pub restore_backup:Option<BackupEntry>, //waiting for the user to confirm
+    pub backup_message:Option<String>
 }
 
 impl Default for GuiInput {
@@ -70,7 +86,21 @@ impl Default for GuiInput {
             device_code: None,
             new_playlist_name: String::new(),
             song_name_to_add: String::new(),
-            add_songs:vec![]
+            add_songs:vec![],
+            new_smart_rules:SmartRules::default(),
+            playlist_import_report:None,
+            export_relative_paths:false,
+            playlist_name_edit:String::new(),
+            playlist_description_edit:String::new(),
+            new_folder_name:String::new(),
+            new_folder_parent:None,
+            renamed_folder:None,
+            folder_name_edit:String::new(),
+            library_view:LibraryView::default(),
+            selection:SongSelection::default(),
+            recovery_messages:vec![],
+            restore_backup:None,
+            backup_message:None
         }
     }
 }
\ No newline at end of file
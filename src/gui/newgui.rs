@@ -1,5 +1,3 @@
-use std::{hash::Hash, collections::hash_map::DefaultHasher};
-use std::hash::Hasher;
 use egui::{Color32, Margin, Pos2, Rect};
 
 use crate::{constants, gui::model::Pages};
@@ -8,20 +6,6 @@ use super::model::Event;
 
 impl eframe::App for crate::app::App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        {
-            let mut db = self.app.database();
-            let new_hash = db.inner.hash();
-            if db.last_save_hash() != new_hash && db.last_save_time().elapsed().as_secs() > 2 {
-                if cfg!(debug_assertions) {
-                    println!("Saving database...");
-                }
-                let _ = db.flush();
-                if cfg!(debug_assertions) {
-                    println!("Saved");
-                }
-            }
-        }
-
         if let Ok(event) = self.gui_settings.event_manager.1.try_recv() {
             match event {
                 Event::SearchResult(tracks) => {
@@ -49,6 +33,9 @@ impl eframe::App for crate::app::App {
                     self.gui_settings.is_logging_in = false;
                     self.gui_settings.should_restart = true;
                 },
+                Event::PlaylistImported(report) => {
+                    self.gui_settings.playlist_import_report = Some(report);
+                },
             }
         }
 
@@ -56,14 +43,12 @@ impl eframe::App for crate::app::App {
             self.app.player.tick();
         }
 
-        let songs = {
-            self.app.database().songs().get_songs()
-        };
-
+        let play_events = self.app.player.take_play_events();
+        if !play_events.is_empty() {
+            let _ = self.app.database().history().add_events(&play_events);
+        }
 
-        let mut hasher = DefaultHasher::new();
-        songs.hash(&mut hasher);
-        let _song_array_hash = hasher.finish();
+        self.handle_undo_shortcuts(ctx);
 
         // if ctx.input(|i| i.key_released(egui::Key::Space)) {
         //     if self.player.is_playing() {
@@ -73,6 +58,22 @@ impl eframe::App for crate::app::App {
         //     }
         // }
         
+        if !self.gui_settings.recovery_messages.is_empty() {
+            egui::Window::new("Recovery")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ctx, |ui| {
+                    for message in &self.gui_settings.recovery_messages {
+                        ui.label(message);
+                    }
+
+                    if ui.button("Dismiss").clicked() {
+                        self.gui_settings.recovery_messages.clear();
+                    }
+                });
+        }
+
         let my_frame = egui::containers::Frame {
             rounding: egui::Rounding { nw: 1.0, ne: 1.0, sw: 1.0, se: 1.0 },
             shadow: eframe::epaint::Shadow::NONE,
@@ -134,6 +135,9 @@ impl eframe::App for crate::app::App {
                         Pages::Search => {
                             self.draw_search_page(ui, container_rect);
                         },
+                        Pages::Library => {
+                            self.draw_library_page(ui, container_rect);
+                        },
                         Pages::Downloads => {
                             self.draw_downloads_page(ui, container_rect);
                         },
@@ -146,5 +150,13 @@ impl eframe::App for crate::app::App {
                     }
                 };
             });
+
+        self.draw_undo_toast(ctx);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        if let Err(e) = self.app.database().raw().close() {
+            println!("Failed to save database : {}", e);
+        }
     }
 }
\ No newline at end of file
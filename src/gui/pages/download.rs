@@ -14,7 +14,24 @@ impl App {
         if downloads.len() == 0 {
             ui.label("No downloads");
         } else {
-            ui.label(format!("{} downloads", downloads.len()));
+            ui.horizontal(|ui| {
+                ui.label(format!("{} downloads", downloads.len()));
+
+                if ui.button("Pause all").clicked() {
+                    self.app.download_manager.pause_all();
+                }
+
+                if ui.button("Resume all").clicked() {
+                    let app = self.app.clone();
+                    tokio::spawn(async move {
+                        app.download_manager.resume_all().await;
+                    });
+                }
+
+                if ui.button("Cancel all").clicked() {
+                    self.app.download_manager.cancel_all();
+                }
+            });
         }
 
         let list_rect = max_rect.expand2(vec2(0., -50.)).expand(-35.);
@@ -38,6 +55,32 @@ impl App {
                   //      progress_bar(ui, download.progress, vec2(ui.available_width() - ui.spacing().item_spacing.x, 30.));
 
                         ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                            let track = download.download.track.clone();
+
+                            if download.status.can_resume() {
+                                let label = if download.status == DownloadStatus::Paused { "Resume" } else { "Retry" };
+                                if ui.button(label).clicked() {
+                                    let app = self.app.clone();
+                                    tokio::spawn(async move {
+                                        if let Err(e) = app.download_manager.resume(&track).await {
+                                            println!("Failed to resume the download of {} : {:?}", track.title, e);
+                                        }
+                                    });
+                                }
+                            }
+
+                            if download.status != DownloadStatus::Finished && download.status != DownloadStatus::Cancelled {
+                                if ui.button("Cancel").on_hover_text("The partially downloaded file is deleted").clicked() {
+                                    self.app.download_manager.cancel(&download.download.track);
+                                }
+                            }
+
+                            if download.status.is_active() || download.status == DownloadStatus::Queued {
+                                if ui.button("Pause").clicked() {
+                                    self.app.download_manager.pause(&download.download.track);
+                                }
+                            }
+
                             ui.add(ProgressBar::new(download.progress).animate(true).show_percentage().fill(Color32::from_rgb(0x1b, 0x6f, 0x06)).desired_width(if 300.0 > ui.available_width() {
                                 ui.available_width()
                             } else {
@@ -1,12 +1,13 @@
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::Instant;
 
 use egui::{vec2, Align, Color32, Id, Label, Layout, Rect, RichText, Rounding, ScrollArea, Widget, Window};
-use crate::{app::App, constants::BACKGROUND_COLOR, gui::{add_button_animated::AddButtonAnimated, helper, model::{Pages, UserLocation}, page::RenderablePage, song::{SongWidget}}, playlist::{DecodedPlaylist, Playlist}, renderer::Drawable, song::Song};
+use crate::{app::App, constants::BACKGROUND_COLOR, gui::{add_button_animated::AddButtonAnimated, components::rules_editor::rules_editor, model::{Event, Pages, UserLocation}, page::RenderablePage, song::{SongWidget}}, playlist::{DecodedPlaylist, Playlist}, playlist_file, renderer::Drawable};
 
 impl App {
     pub fn draw_home_page(&mut self, ui:&mut egui::Ui, max_rect:Rect) {
         let mut ui = ui.child_ui(max_rect, Layout::top_down(Align::LEFT));
         let new_playlist = Id::new("_new_playlist");
+        let new_smart_playlist = Id::new("_new_smart_playlist");
         let add_song_to_playlist = Id::new("_add_song_to_playlist");
 
         if ui.memory(|mem| mem.data.get_temp(add_song_to_playlist).unwrap_or(false)) == true {
@@ -16,7 +17,7 @@ impl App {
                     ui.text_edit_singleline(&mut self.gui_settings.song_name_to_add);
                     if ui.button("Add Songs").clicked() {
                         if let UserLocation::Playlist(playlist) = &self.gui_settings.location {
-                            self.app.database().playlists().push_to_playlist(&playlist, &self.gui_settings.add_songs);
+                            let _ = self.app.database().playlists().push_to_playlist(&playlist, &self.gui_settings.add_songs);
                             self.gui_settings.add_songs.clear();
                         }
         
@@ -29,30 +30,12 @@ impl App {
                 });
 
 
-                let library = {
-                    self.app.database().songs().get_songs()
+                //title, artist and album, prefixes like artist:daft narrow the search to one field
+                let result = {
+                    let database = self.app.database();
+                    self.app.search_index.lock().unwrap().search(&database, &self.gui_settings.song_name_to_add)
                 };
 
-                let result = library.into_iter().filter(|item| {
-                    if self.gui_settings.song_name_to_add.is_empty() {
-                        return true;
-                    }
-
-                    if helper::simplify(&item.get_title()).contains(&self.gui_settings.song_name_to_add.to_lowercase()) {
-                        return true;
-                    }
-
-                    if helper::simplify(&item.get_artist()).to_lowercase().contains(&self.gui_settings.song_name_to_add.to_lowercase()) {
-                        return true;
-                    }
-
-                    if item.get_album().and_then(|album| Some(helper::simplify(&album.title).contains(&self.gui_settings.song_name_to_add.to_lowercase()))).unwrap_or(false) {
-                        return true;
-                    }
-
-                    return false;
-                }).collect::<Vec<Song>>();
-
 
 
                 ScrollArea::new([false, true]).show(ui, |ui| {
@@ -90,11 +73,13 @@ impl App {
                 });
                 ui.horizontal(|ui| {
                     if !self.gui_settings.new_playlist_name.is_empty() && ui.button("Create").clicked() {
-                        self.app.database().playlists().add_playlist(&Playlist {
-                            id: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string(),
+                        let _ = self.app.database().playlists().add_playlist(&Playlist {
+                            id: Playlist::generate_id(),
                             name: self.gui_settings.new_playlist_name.clone(),
                             image: None, //TODO
+                            description: None,
                             songs: vec![], //TODO
+                            rules: None
                         });
                         
                         ui.memory_mut(|mem| mem.data.remove::<bool>(new_playlist));
@@ -108,6 +93,54 @@ impl App {
         }
         
         
+        if ui.memory(|mem| mem.data.get_temp(new_smart_playlist).unwrap_or(false)) == true {
+            Window::new("New smart playlist").show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Name").strong());
+                    ui.text_edit_singleline(&mut self.gui_settings.new_playlist_name);
+                });
+
+                rules_editor(ui, &mut self.gui_settings.new_smart_rules);
+
+                ui.horizontal(|ui| {
+                    if !self.gui_settings.new_playlist_name.is_empty() && ui.button("Create").clicked() {
+                        let _ = self.app.database().playlists().add_playlist(&Playlist {
+                            id: Playlist::generate_id(),
+                            name: self.gui_settings.new_playlist_name.clone(),
+                            image: None,
+                            description: None,
+                            songs: vec![],
+                            rules: Some(std::mem::take(&mut self.gui_settings.new_smart_rules))
+                        });
+
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(new_smart_playlist));
+                    };
+
+                    if ui.button("Cancel").clicked() {
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(new_smart_playlist));
+                    }
+                });
+            });
+        }
+
+        if let Some(report) = self.gui_settings.playlist_import_report.clone() {
+            Window::new("Playlist import").show(ui.ctx(), |ui| {
+                ui.label(format!("{} : {} songs imported, {} not found", report.playlist_name, report.matched, report.unresolved.len()));
+
+                if report.unresolved.len() > 0 {
+                    ScrollArea::new([false, true]).id_source("_unresolved_entries_").max_height(200.).show(ui, |ui| {
+                        report.unresolved.iter().for_each(|entry| {
+                            ui.label(entry);
+                        });
+                    });
+                }
+
+                if ui.button("Close").clicked() {
+                    self.gui_settings.playlist_import_report = None;
+                }
+            });
+        }
+
         let add_pos = ui.max_rect().max - vec2(60., 3.0);
         let add_rect = Rect::from_center_size(add_pos, vec2(40., 40.));
 
@@ -115,25 +148,25 @@ impl App {
         match self.gui_settings.location.clone() {
             crate::gui::model::UserLocation::Home => {
 
-                let playlists = {
-                    self.app.database().playlists().get_playlists()
+                self.draw_playlist_tree(&mut ui);
+
+                let artists = {
+                    self.app.database().artists().get_artists()
                 };
 
-                ui.label(RichText::new("Playlists").strong());
-                ScrollArea::new([false, true]).id_source("_playlists_").show(&mut ui, |ui| {
-                    playlists.into_iter().for_each(|playlist| {
-                        if ui.button(playlist.name.clone()).clicked() {
-                            self.gui_settings.location = UserLocation::Playlist(
-                                playlist
-                            );
-                            
-                            self.gui_settings.page = Pages::Playlist;
+                ui.label(RichText::new("Artists").strong());
+                ScrollArea::new([false, true]).id_source("_artists_").show(&mut ui, |ui| {
+                    artists.into_iter().for_each(|artist| {
+                        if ui.button(artist.name.clone()).clicked() {
+                            self.gui_settings.location = UserLocation::Artist(artist);
+
+                            self.gui_settings.page = Pages::Artist;
                         }
                     });
                 });
 
                 let albums = {
-                    self.app.database().albums().get_albums(self.app.clone())
+                    self.app.database().albums().get_albums()
                 };
 
                 ui.label(RichText::new("Albums").strong());
@@ -167,7 +200,9 @@ impl App {
             crate::gui::model::UserLocation::Playlist(playlist_descriptor) => {
                 playlist_descriptor.render(self, &mut ui, max_rect);
             },
-            crate::gui::model::UserLocation::Artist( _) => todo!(),
+            crate::gui::model::UserLocation::Artist(artist) => {
+                artist.render(self, &mut ui, max_rect);
+            },
             crate::gui::model::UserLocation::Album(album) => {
                 album.render(self, &mut ui, max_rect);
             },
@@ -187,7 +222,33 @@ impl App {
                     ui.memory_mut(|mem| mem.data.insert_temp(new_playlist, true));
                 }
 
-                if self.gui_settings.page == Pages::Playlist {
+                if ui.button("New smart playlist").clicked() {
+                    ui.memory_mut(|mem| mem.data.insert_temp(new_smart_playlist, true));
+                }
+
+                if ui.button("Import playlist").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("Playlist", &["m3u8", "m3u", "pls", "xspf"]).pick_file() {
+                        let app = self.app.clone();
+                        let tx = self.gui_settings.event_manager.0.clone();
+
+                        tokio::task::spawn_blocking(move || {
+                            match playlist_file::import(&app, &path) {
+                                Ok(report) => {
+                                    let _ = tx.blocking_send(Event::PlaylistImported(report));
+                                },
+                                Err(e) => println!("Failed to import playlist : {}", e)
+                            }
+                        });
+                    }
+                }
+
+                //smart playlists are filled by their rules
+                let is_smart_playlist = match &self.gui_settings.location {
+                    UserLocation::Playlist(playlist) => playlist.smart,
+                    _ => false
+                };
+
+                if self.gui_settings.page == Pages::Playlist && !is_smart_playlist {
                     if ui.button("Add song to playlist").clicked() {
                         ui.memory_mut(|mem| mem.data.insert_temp(add_song_to_playlist, true));
                     }
@@ -0,0 +1,308 @@
+use std::{cmp::Ordering, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
+
+use egui::{Layout, Rect, RichText, Sense};
+use egui_extras::{Column, TableBuilder};
+
+use crate::{app::App, constants::ACTION_COLOR, gui::helper::simplify, smart_playlist::is_lossless, song::Song, time};
+
+const ROW_HEIGHT: f32 = 22.0;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq)]
+pub enum LibraryColumn {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    Quality,
+    Added,
+    PlayCount
+}
+
+impl LibraryColumn {
+    pub const ALL: [LibraryColumn; 7] = [
+        LibraryColumn::Title,
+        LibraryColumn::Artist,
+        LibraryColumn::Album,
+        LibraryColumn::Duration,
+        LibraryColumn::Quality,
+        LibraryColumn::Added,
+        LibraryColumn::PlayCount
+    ];
+
+    fn index(&self) -> usize {
+        LibraryColumn::ALL.iter().position(|column| column == self).unwrap_or(0)
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LibraryColumn::Title => "Title",
+            LibraryColumn::Artist => "Artist",
+            LibraryColumn::Album => "Album",
+            LibraryColumn::Duration => "Duration",
+            LibraryColumn::Quality => "Quality",
+            LibraryColumn::Added => "Added",
+            LibraryColumn::PlayCount => "Plays"
+        }
+    }
+
+    fn initial_width(&self) -> f32 {
+        match self {
+            LibraryColumn::Title | LibraryColumn::Artist | LibraryColumn::Album => 180.0,
+            LibraryColumn::Added => 100.0,
+            _ => 70.0
+        }
+    }
+
+    fn compare(&self, a:&LibraryRow, b:&LibraryRow) -> Ordering {
+        match self {
+            LibraryColumn::Title | LibraryColumn::Artist | LibraryColumn::Album => a.folded[self.index()].cmp(&b.folded[self.index()]),
+            LibraryColumn::Duration => a.song.duration.cmp(&b.song.duration),
+            LibraryColumn::Quality => a.lossless.cmp(&b.lossless).then_with(|| a.texts[self.index()].cmp(&b.texts[self.index()])),
+            LibraryColumn::Added => a.song.added_at.cmp(&b.song.added_at),
+            LibraryColumn::PlayCount => a.play_count.cmp(&b.play_count)
+        }
+    }
+}
+
+//texts are computed once per song so sorting and filtering large libraries stays cheap
+struct LibraryRow {
+    song:Song,
+    play_count:u64,
+    lossless:bool,
+    texts:Vec<String>,
+    folded:Vec<String>
+}
+
+impl LibraryRow {
+    fn new(song:Song, play_count:u64) -> Self {
+        let now = time::now();
+        let lossless = is_lossless(&song);
+
+        let texts = LibraryColumn::ALL.iter().map(|column| match column {
+            LibraryColumn::Title => song.title.clone(),
+            LibraryColumn::Artist => song.artist.clone(),
+            LibraryColumn::Album => song.album.clone(),
+            LibraryColumn::Duration => song.duration.map(time::ms_to_min_sec).unwrap_or_default(),
+            LibraryColumn::Quality => song.path.extension().map(|extension| extension.to_string_lossy().to_uppercase()).unwrap_or_default(),
+            LibraryColumn::Added => song.added_at.map(|added_at| time::secs_to_age(now.saturating_sub(added_at))).unwrap_or_default(),
+            LibraryColumn::PlayCount => play_count.to_string()
+        }).collect::<Vec<String>>();
+
+        let folded = texts.iter().map(|text| simplify(text)).collect();
+
+        LibraryRow { song, play_count, lossless, texts, folded }
+    }
+}
+
+pub struct LibraryView {
+    pub sort:Vec<(LibraryColumn, bool)>, //columns to sort by in order of priority, true when ascending
+    pub filters:Vec<String>, //one per column
+    revision:Option<i64>,
+    rows:Vec<LibraryRow>,
+    visible:Vec<usize>, //indexes of the rows that pass the filters, sorted
+    visible_key:Option<u64>
+}
+
+impl Default for LibraryView {
+    fn default() -> Self {
+        LibraryView {
+            sort: vec![(LibraryColumn::Artist, true), (LibraryColumn::Album, true)],
+            filters: vec![String::new(); LibraryColumn::ALL.len()],
+            revision: None,
+            rows: vec![],
+            visible: vec![],
+            visible_key: None
+        }
+    }
+}
+
+impl LibraryView {
+    fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.sort.hash(&mut hasher);
+        self.filters.hash(&mut hasher);
+        self.revision.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    //clicking a column sorts by it alone, shift-clicking adds it after the current ones
+    fn toggle_sort(&mut self, column:LibraryColumn, add:bool) {
+        match self.sort.iter().position(|(sorted, _)| *sorted == column) {
+            Some(position) if add || position == 0 => self.sort[position].1 = !self.sort[position].1,
+            _ if add => self.sort.push((column, true)),
+            _ => self.sort = vec![(column, true)]
+        }
+
+        if !add {
+            self.sort.truncate(1);
+        }
+    }
+
+    fn update_visible(&mut self) {
+        let key = self.key();
+        if self.visible_key == Some(key) {
+            return;
+        }
+
+        let filters = self.filters.iter().map(|filter| simplify(filter.trim())).collect::<Vec<String>>();
+
+        let mut visible = self.rows.iter().enumerate()
+            .filter(|(_, row)| filters.iter().enumerate().all(|(index, filter)| filter.is_empty() || row.folded[index].contains(filter.as_str())))
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+
+        visible.sort_by(|a, b| {
+            let (a, b) = (&self.rows[*a], &self.rows[*b]);
+
+            self.sort.iter().fold(Ordering::Equal, |ordering, (column, ascending)| {
+                ordering.then_with(|| if *ascending { column.compare(a, b) } else { column.compare(b, a) })
+            })
+        });
+
+        self.visible = visible;
+        self.visible_key = Some(key);
+    }
+
+    fn sort_indicator(&self, column:LibraryColumn) -> String {
+        match self.sort.iter().position(|(sorted, _)| *sorted == column) {
+            Some(position) => {
+                let arrow = if self.sort[position].1 { "⏶" } else { "⏷" };
+                if self.sort.len() > 1 { format!(" {}{}", arrow, position + 1) } else { format!(" {}", arrow) }
+            },
+            None => String::new()
+        }
+    }
+}
+
+impl App {
+    pub fn draw_library_page(&mut self, ui:&mut egui::Ui, max_rect:Rect) {
+        let mut ui = ui.child_ui(max_rect.shrink(15.), Layout::default());
+
+        let revision = {
+            self.app.database().songs().revision()
+        };
+
+        if revision.is_none() || revision != self.gui_settings.library_view.revision {
+            let (songs, stats) = {
+                let database = self.app.database();
+                (database.songs().get_songs(), database.history().get_all_stats())
+            };
+
+            self.gui_settings.library_view.rows = songs.into_iter().map(|song| {
+                let play_count = stats.get(&song.id).map(|stats| stats.play_count).unwrap_or(0);
+                LibraryRow::new(song, play_count)
+            }).collect();
+
+            self.gui_settings.library_view.revision = revision;
+        }
+
+        self.gui_settings.library_view.update_visible();
+
+        let view = &mut self.gui_settings.library_view;
+
+        ui.horizontal(|ui| {
+            ui.heading("Library");
+            ui.label(format!("{} of {} songs", view.visible.len(), view.rows.len()));
+
+            if view.filters.iter().any(|filter| !filter.is_empty()) && ui.button("Clear filters").clicked() {
+                view.filters.iter_mut().for_each(|filter| filter.clear());
+            }
+        });
+
+        let mut clicked_column = None;
+        let mut clicked_row = None;
+        let mut played_row = None;
+
+        let mut table = TableBuilder::new(&mut ui)
+            .striped(true)
+            .resizable(true)
+            .auto_shrink([false, false])
+            .cell_layout(Layout::left_to_right(egui::Align::Center));
+
+        for column in LibraryColumn::ALL {
+            table = table.column(Column::initial(column.initial_width()).at_least(40.0).resizable(true).clip(true));
+        }
+
+        let row_count = view.visible.len();
+
+        table
+            .header(46.0, |mut header| {
+                let view = &mut self.gui_settings.library_view;
+
+                for column in LibraryColumn::ALL {
+                    header.col(|ui| {
+                        ui.vertical(|ui| {
+                            let label = format!("{}{}", column.name(), view.sort_indicator(column));
+                            if ui.add(egui::Label::new(RichText::new(label).strong()).sense(Sense::click())).on_hover_text("Shift-click to sort by several columns").clicked() {
+                                clicked_column = Some(column);
+                            }
+
+                            ui.add(egui::TextEdit::singleline(&mut view.filters[column.index()]).hint_text("Filter"));
+                        });
+                    });
+                }
+            })
+            .body(|body| {
+                //only the rows on screen are laid out
+                body.rows(ROW_HEIGHT, row_count, |mut row| {
+                    let position = row.index();
+
+                    let (song, texts) = {
+                        let view = &self.gui_settings.library_view;
+                        let library_row = &view.rows[view.visible[position]];
+                        (library_row.song.clone(), library_row.texts.clone())
+                    };
+
+                    let selected = self.gui_settings.selection.is_selected("library", &song);
+
+                    for column in LibraryColumn::ALL {
+                        row.col(|ui| {
+                            let text = RichText::new(&texts[column.index()]);
+                            let text = if selected { text.color(ACTION_COLOR) } else { text };
+
+                            if column == LibraryColumn::Title {
+                                let response = ui.add(egui::Label::new(text).truncate(true).sense(Sense::click()));
+
+                                if response.double_clicked() {
+                                    played_row = Some(position);
+                                } else if response.clicked() {
+                                    clicked_row = Some(position);
+                                }
+
+                                song.context_menu(response, ui, self);
+                            } else {
+                                ui.add(egui::Label::new(text).truncate(true));
+                            }
+                        });
+                    }
+                });
+            });
+
+        let view = &mut self.gui_settings.library_view;
+
+        if let Some(column) = clicked_column {
+            let add = ui.input(|input| input.modifiers.shift);
+            view.toggle_sort(column, add);
+        }
+
+        let songs = if clicked_row.is_some() || played_row.is_some() {
+            view.visible.iter().map(|index| view.rows[*index].song.clone()).collect::<Vec<Song>>()
+        } else {
+            vec![]
+        };
+
+        if let Some(position) = clicked_row {
+            let modifiers = ui.input(|input| input.modifiers);
+            self.gui_settings.selection.handle_click("library", &songs, position, modifiers);
+        }
+
+        //the queue becomes the library as it is shown, starting at the song
+        if let Some(position) = played_row {
+            let _ = self.app.player.set_media(&songs[position]);
+
+            let mut queue = self.app.player.queue();
+            queue.set_playlist(&songs);
+            queue.current_index = Some(position);
+        }
+    }
+}
@@ -3,5 +3,6 @@ pub mod download;
 pub mod home;
 pub mod search;
 pub mod settings;
+pub mod library;
 pub mod navbar;
 pub mod controls;
\ No newline at end of file
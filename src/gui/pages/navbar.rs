@@ -2,7 +2,7 @@ use egui::{Rect, Pos2, Rounding, Vec2, include_image, Image, Sense, vec2};
 use tidal_rs::model::User;
 use crate::{app::App, constants::BACKGROUND_COLOR, gui::model::{Pages, UserLocation}};
 
-const ICONS: usize = 4;
+const ICONS: usize = 5;
 
 fn calculate_icon_positions(icon_size: Vec2, rect: Rect) -> Vec<Rect> {
     let interval = rect.height() / (ICONS as f32 - 1.0);
@@ -29,7 +29,7 @@ impl App {
         let min_panel_y = max_rect.min.y + height * 0.05;
     
         const PANEL_WIDTH: f32 = 65.0;
-        const PANEL_HEIGHT: f32 = 220.0; // Adjusted panel height to accommodate 4 elements
+        const PANEL_HEIGHT: f32 = 270.0; // Adjusted panel height to accommodate 5 elements
     
         let min_panel: Pos2 = Pos2 {
             x: min_panel_center_x - PANEL_WIDTH / 2.0,
@@ -60,11 +60,15 @@ impl App {
               self.gui_settings.page = Pages::Search;
           }
 
-          if ui.put(positions[2], Image::new(include_image!("../../../assets/download.svg")).sense(Sense::click()).fit_to_exact_size(icon_size)).clicked() {
+          if ui.put(positions[2], Image::new(include_image!("../../../assets/library.svg")).sense(Sense::click()).fit_to_exact_size(icon_size)).clicked() {
+              self.gui_settings.page = Pages::Library;
+          }
+
+          if ui.put(positions[3], Image::new(include_image!("../../../assets/download.svg")).sense(Sense::click()).fit_to_exact_size(icon_size)).clicked() {
               self.gui_settings.page = Pages::Downloads;
           }
 
-          if ui.put(positions[3], Image::new(include_image!("../../../assets/settings.svg")).sense(Sense::click()).fit_to_exact_size(icon_size)).clicked() {
+          if ui.put(positions[4], Image::new(include_image!("../../../assets/settings.svg")).sense(Sense::click()).fit_to_exact_size(icon_size)).clicked() {
               self.gui_settings.page = Pages::Settings;
           }
 
@@ -92,6 +92,13 @@ impl App {
                     }
                 };
 
+                //tracks that are already in the library, they are the ones that can be selected
+                let songs = if self.gui_settings.search_type == SearchType::Track {
+                    items.iter().filter_map(|item| item.get_track()).filter_map(|track| Song::resolve(self.app.clone(), &track)).collect::<Vec<Song>>()
+                } else {
+                    vec![]
+                };
+
                 items.into_iter().for_each(|item| {
                     ui.horizontal(|ui| {
                         let response = ui.add(
@@ -100,15 +107,26 @@ impl App {
                             )
                         );
 
-                        if self.gui_settings.search_type == SearchType::Track {
-                            let track = item.get_track().unwrap();
+                        let song = item.get_track().and_then(|track| {
+                            let id = Song::generate_id(Some(&track));
+                            songs.iter().position(|song| song.id == id)
+                        });
 
-                            if let Some(song) = Song::resolve(self.app.clone(), &track) {
-                                song.context_menu(response, ui, &self);
-                            }   
-                        }
+                        match song {
+                            Some(index) => {
+                                let song = &songs[index];
 
-                        ui.label(item.get_title());
+                                if ui.selectable_label(self.gui_settings.selection.is_selected("search", song), item.get_title()).clicked() {
+                                    let modifiers = ui.input(|input| input.modifiers);
+                                    self.gui_settings.selection.handle_click("search", &songs, index, modifiers);
+                                }
+
+                                song.context_menu(response, ui, self);
+                            },
+                            None => {
+                                ui.label(item.get_title());
+                            }
+                        }
                         if ui.button("Download").clicked() {
                             let app = self.app.clone();
           
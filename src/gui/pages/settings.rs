@@ -1,8 +1,8 @@
 use std::time::{Duration, Instant};
 
-use egui::{include_image, pos2, vec2, Align2, Color32, ComboBox, FontId, Image, Layout, OpenUrl, Rect, Rounding, Sense};
+use egui::{include_image, pos2, vec2, Align2, Color32, ComboBox, FontId, Image, Layout, OpenUrl, ProgressBar, Rect, Rounding, Sense};
 
-use crate::{app::App, constants::WARNING_COLOR};
+use crate::{app::App, constants::WARNING_COLOR, library::{AuditStatus, ScanStatus}, time, trash};
 
 impl App {
     pub fn draw_settings_page(&mut self, ui:&mut egui::Ui, max_rect:Rect) {
@@ -103,6 +103,21 @@ impl App {
             }
         });
 
+        ui.horizontal(|ui| {
+            ui.label("Retries of a failed download : ");
+            let mut configuration = self.app.configuration.lock().unwrap();
+            let response = ui.add(egui::Slider::new(&mut configuration.download_retries, 0..=10));
+
+            if response.changed() {
+                self.app.download_manager.set_max_retries(configuration.download_retries);
+            }
+
+            //the file is written once the slider is let go, not at every step of the drag
+            if response.drag_released() || response.lost_focus() {
+                configuration.flush();
+            }
+        });
+
         ui.horizontal(|ui| {
             ui.label("Download quality (maximal) : ");
             //combobox with all the qualities
@@ -119,5 +134,214 @@ impl App {
             }
 
         });
+
+        ui.separator();
+
+        let scan_progress = self.app.library_scanner.progress();
+
+        ui.horizontal(|ui| {
+            ui.label("Library : ");
+            if ui.add_enabled(scan_progress.status != ScanStatus::Scanning, egui::Button::new("Import music folders")).clicked() {
+                if let Some(folders) = rfd::FileDialog::new().pick_folders() {
+                    let mut configuration = self.app.configuration.lock().unwrap();
+                    for folder in &folders {
+                        configuration.add_library_folder(folder.clone());
+                        let _ = self.app.library_watcher.watch(folder);
+                    }
+                    configuration.flush();
+                    drop(configuration); //release lock
+
+                    self.app.library_scanner.scan(self.app.clone(), folders);
+                }
+            }
+        });
+
+        let library_folders = {
+            self.app.configuration.lock().unwrap().library_folders.clone()
+        };
+
+        for folder in library_folders {
+            ui.horizontal(|ui| {
+                ui.label(folder.display().to_string());
+                if ui.button("Stop watching").clicked() {
+                    let mut configuration = self.app.configuration.lock().unwrap();
+                    configuration.remove_library_folder(&folder);
+                    configuration.flush();
+
+                    let _ = self.app.library_watcher.unwatch(&folder);
+                }
+            });
+        }
+
+        match &scan_progress.status {
+            ScanStatus::Idle => {},
+            ScanStatus::Scanning => {
+                ui.add(ProgressBar::new(scan_progress.progress()).text(format!("{}/{} files", scan_progress.scanned, scan_progress.total)));
+                if let Some(current) = &scan_progress.current {
+                    ui.label(current.display().to_string());
+                }
+            },
+            ScanStatus::Finished => {
+                ui.label(format!("Imported {} songs ({} files could not be read)", scan_progress.imported, scan_progress.failed));
+            },
+            ScanStatus::Failed(_) => {
+                ui.label(scan_progress.status.to_string());
+            }
+        }
+
+        ui.separator();
+
+        let audit = self.app.library_audit.state();
+
+        ui.horizontal(|ui| {
+            ui.label("Missing files : ");
+            if ui.add_enabled(!self.app.library_audit.is_busy(), egui::Button::new("Check library")).clicked() {
+                self.app.library_audit.find_missing(self.app.clone());
+            }
+
+            if audit.missing.len() > 0 && ui.add_enabled(!self.app.library_audit.is_busy(), egui::Button::new("Search in folder")).clicked() {
+                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                    self.app.library_audit.search(folder);
+                }
+            }
+        });
+
+        match audit.status {
+            AuditStatus::Idle => {},
+            AuditStatus::Checking => {
+                ui.label("Checking library...");
+            },
+            AuditStatus::Searching => {
+                let progress = if audit.total == 0 { 0.0 } else { audit.scanned as f32 / audit.total as f32 };
+                ui.add(ProgressBar::new(progress).text(format!("{}/{} files", audit.scanned, audit.total)));
+            },
+            AuditStatus::Done => {
+                ui.label(format!("{} songs are missing, {} were found", audit.missing.len(), audit.relocations.len()));
+
+                if audit.relocations.len() > 0 {
+                    egui::ScrollArea::new([false, true]).id_source("_relocations_").max_height(200.).show(&mut ui, |ui| {
+                        audit.relocations.iter().enumerate().for_each(|(index, relocation)| {
+                            let mut accepted = relocation.accepted;
+                            if ui.checkbox(&mut accepted, format!("{} - {} => {}", relocation.song.title, relocation.song.artist, relocation.new_path.display())).changed() {
+                                self.app.library_audit.set_accepted(index, accepted);
+                            }
+                        });
+                    });
+
+                    if ui.button("Relocate selected songs").clicked() {
+                        let _ = self.app.library_audit.apply(self.app.clone());
+                    }
+                }
+            }
+        }
+
+        ui.separator();
+
+        let trash_entries = {
+            self.app.database().trash().get_entries()
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Trash : {} songs", trash_entries.len()));
+            if trash_entries.len() > 0 && ui.button("Empty trash").clicked() {
+                let _ = trash::empty(&self.app);
+            }
+        });
+
+        egui::ScrollArea::new([false, true]).id_source("_trash_").max_height(200.).show(&mut ui, |ui| {
+            trash_entries.iter().for_each(|entry| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} - {}", entry.song.title, entry.song.artist));
+                    if ui.button("Restore").clicked() {
+                        let _ = trash::restore(&self.app, entry);
+                    }
+
+                    if ui.button("Delete").clicked() {
+                        let _ = trash::delete_permanently(&self.app, entry);
+                    }
+                });
+            });
+        });
+
+        ui.separator();
+
+        let backups = {
+            self.app.database().raw().get_backups()
+        };
+
+        ui.horizontal(|ui| {
+            ui.label(format!("Library backups : {}", backups.len()));
+            if ui.button("Back up now").clicked() {
+                let compress_backups = self.app.configuration.lock().unwrap().compress_backups;
+                if let Err(e) = self.app.database().raw().backup(compress_backups) {
+                    println!("Failed to back up database : {}", e);
+                    self.gui_settings.backup_message = Some(format!("The library could not be backed up : {}", e));
+                }
+            }
+
+            let mut configuration = self.app.configuration.lock().unwrap();
+            if ui.checkbox(&mut configuration.compress_backups, "Compress backups").changed() {
+                configuration.flush();
+            }
+
+            //the library is converted when it is opened
+            if ui.checkbox(&mut configuration.compress_database, "Compress library").changed() {
+                configuration.flush();
+                self.gui_settings.should_restart = true;
+            }
+        });
+
+        if let Some(message) = self.gui_settings.backup_message.clone() {
+            ui.horizontal(|ui| {
+                ui.label(message);
+                if ui.button("Dismiss").clicked() {
+                    self.gui_settings.backup_message = None;
+                }
+            });
+        }
+
+        egui::ScrollArea::new([false, true]).id_source("_backups_").max_height(200.).show(&mut ui, |ui| {
+            backups.iter().for_each(|entry| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Backup from {}", time::secs_to_age(time::now().saturating_sub(entry.created_at))));
+                    if ui.button("Restore").clicked() {
+                        self.gui_settings.restore_backup = Some(entry.clone());
+                    }
+                });
+            });
+        });
+
+        //restoring replaces the whole library, it is only done once confirmed
+        if let Some(entry) = self.gui_settings.restore_backup.clone() {
+            egui::Window::new("Restore backup")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(ui.ctx(), |ui| {
+                    ui.label(format!("The library will be replaced by the backup from {}.", time::secs_to_age(time::now().saturating_sub(entry.created_at))));
+                    ui.label("The current library is backed up first, restoring that backup undoes this.");
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            let result = {
+                                self.app.database().raw().restore_backup(&entry)
+                            };
+
+                            self.gui_settings.backup_message = Some(match result {
+                                Ok(previous_library) => format!("The backup was restored, the previous library was saved to {}.", previous_library.display()),
+                                Err(e) => {
+                                    println!("Failed to restore backup : {}", e);
+                                    format!("The backup could not be restored : {}", e)
+                                }
+                            });
+                            self.gui_settings.restore_backup = None;
+                        }
+
+                        if ui.button("Cancel").clicked() {
+                            self.gui_settings.restore_backup = None;
+                        }
+                    });
+                });
+        }
     }
 }
\ No newline at end of file
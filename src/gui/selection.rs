@@ -0,0 +1,65 @@
+use egui::Modifiers;
+
+use crate::song::Song;
+
+//songs selected with ctrl and shift clicks, only in one list at a time
+#[derive(Default)]
+pub struct SongSelection {
+    list:Option<String>,
+    songs:Vec<Song>,
+    anchor:Option<usize> //index of the last clicked song, where shift ranges start
+}
+
+impl SongSelection {
+    pub fn is_selected(&self, list:&str, song:&Song) -> bool {
+        self.list.as_deref() == Some(list) && self.songs.iter().any(|selected| selected.id == song.id)
+    }
+
+    pub fn songs(&self) -> &Vec<Song> {
+        &self.songs
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.songs.clear();
+        self.anchor = None;
+    }
+
+    //returns true when the click changed the selection, a plain click clears it and should play the song instead
+    pub fn handle_click(&mut self, list:&str, songs:&[Song], index:usize, modifiers:Modifiers) -> bool {
+        if self.list.as_deref() != Some(list) {
+            self.list = Some(list.to_string());
+            self.clear();
+        }
+
+        let Some(song) = songs.get(index) else {
+            return false;
+        };
+
+        if modifiers.command {
+            match self.songs.iter().position(|selected| selected.id == song.id) {
+                Some(position) => { self.songs.remove(position); },
+                None => self.songs.push(song.clone())
+            }
+
+            self.anchor = Some(index);
+            return true;
+        }
+
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(index).min(songs.len() - 1);
+            let range = if anchor <= index { anchor..=index } else { index..=anchor };
+
+            self.songs = songs[range].to_vec();
+            return true;
+        }
+
+        self.clear();
+        self.anchor = Some(index);
+
+        false
+    }
+}
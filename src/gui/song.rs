@@ -7,6 +7,7 @@ use crate::app::{App, AppImpl};
 use crate::constants::{TEXT_COLOR, self};
 use crate::renderer::Drawable;
 use crate::song::Song;
+use crate::undo::{self, Command};
 
 use super::helper;
 
@@ -105,7 +106,9 @@ pub fn song_context_menu(app:Arc<AppImpl>, ui:&mut egui::Ui, song:&Song) {
     }
 
     if ui.button("Delete").clicked() {
-        let _ = app.database().songs().remove_song(song.clone());
+        if let Err(e) = undo::execute(&app, Command::remove_songs(vec![song.clone()])) {
+            println!("Failed to remove song : {}", e);
+        }
         ui.close_menu();
     }
     
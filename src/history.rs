@@ -0,0 +1,77 @@
+use std::time::{Duration, Instant};
+
+use crate::{song::Song, time};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayEventKind {
+    Start,
+    Skip,
+    Complete
+}
+
+impl PlayEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlayEventKind::Start => "start",
+            PlayEventKind::Skip => "skip",
+            PlayEventKind::Complete => "complete"
+        }
+    }
+
+    pub fn from_str(kind:&str) -> Option<Self> {
+        match kind {
+            "start" => Some(PlayEventKind::Start),
+            "skip" => Some(PlayEventKind::Skip),
+            "complete" => Some(PlayEventKind::Complete),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayEvent {
+    pub song_id:String,
+    pub kind:PlayEventKind,
+    pub timestamp:u64,
+    pub listened:u64 //ms
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SongStats {
+    pub play_count:u64,
+    pub last_played:Option<u64>
+}
+
+//the song being listened to, time only counts while the player is playing
+pub struct ListeningSession {
+    pub song:Song,
+    pub listened:Duration,
+    pub last_tick:Instant
+}
+
+impl ListeningSession {
+    pub fn new(song:&Song) -> Self {
+        ListeningSession {
+            song: song.clone(),
+            listened: Duration::ZERO,
+            last_tick: Instant::now()
+        }
+    }
+
+    pub fn tick(&mut self, is_playing:bool) {
+        if is_playing {
+            self.listened += self.last_tick.elapsed();
+        }
+
+        self.last_tick = Instant::now();
+    }
+
+    pub fn to_event(&self, kind:PlayEventKind) -> PlayEvent {
+        PlayEvent {
+            song_id: self.song.id.clone(),
+            kind,
+            timestamp: time::now(),
+            listened: self.listened.as_millis() as u64
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::{collections::HashSet, path::{Path, PathBuf}, sync::{Arc, Mutex}};
+
+use lofty::{Accessor, AudioFile, Probe, TaggedFileExt};
+use walkdir::WalkDir;
+
+use crate::{app::AppImpl, gui::helper::simplify, song::Song, tagging};
+
+pub const AUDIO_EXTENSIONS: [&str; 8] = ["flac", "mp3", "m4a", "mp4", "aac", "ogg", "opus", "wav"];
+
+#[derive(Clone, PartialEq)]
+pub enum ScanStatus {
+    Idle,
+    Scanning,
+    Finished,
+    Failed(String)
+}
+
+impl ToString for ScanStatus {
+    fn to_string(&self) -> String {
+        match self {
+            ScanStatus::Idle => "Idle".to_string(),
+            ScanStatus::Scanning => "Scanning".to_string(),
+            ScanStatus::Finished => "Finished".to_string(),
+            ScanStatus::Failed(message) => format!("Failed with message : {}", message)
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScanProgress {
+    pub status:ScanStatus,
+    pub total:usize,
+    pub scanned:usize,
+    pub imported:usize,
+    pub failed:usize,
+    pub current:Option<PathBuf>
+}
+
+impl Default for ScanProgress {
+    fn default() -> Self {
+        ScanProgress {
+            status: ScanStatus::Idle,
+            total: 0,
+            scanned: 0,
+            imported: 0,
+            failed: 0,
+            current: None
+        }
+    }
+}
+
+impl ScanProgress {
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.scanned as f32 / self.total as f32
+    }
+}
+
+pub fn is_audio_file(path:&Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| AUDIO_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+pub fn find_audio_files(folder:&Path) -> Vec<PathBuf> {
+    WalkDir::new(folder)
+        .follow_links(true)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && is_audio_file(entry.path()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+//reads the tags of an audio file, returns the song, the embedded cover if there is one and the tidal id written by the downloads
+pub fn read_song(path:&Path) -> Result<(Song, Option<Vec<u8>>, Option<u64>), lofty::LoftyError> {
+    let tagged_file = Probe::open(path)?.read()?;
+    let tag = tagged_file.primary_tag().or_else(|| tagged_file.first_tag());
+
+    let file_name = path.file_stem().map(|x| x.to_string_lossy().to_string()).unwrap_or("Unknown".to_string());
+
+    let title = tag.and_then(|tag| tag.title().map(|x| x.to_string())).unwrap_or(file_name);
+    let artist = tag.and_then(|tag| tag.artist().map(|x| x.to_string())).unwrap_or("Unknown".to_string());
+    let album = tag.and_then(|tag| tag.album().map(|x| x.to_string())).unwrap_or("Unknown".to_string());
+
+    let mut song = Song::new(path.to_path_buf(), title, artist, album);
+    song.track_number = tag.and_then(|tag| tag.track());
+    song.duration = Some(tagged_file.properties().duration().as_millis() as u64);
+    song.lossless = Some(tagging::is_lossless(tagged_file.properties()));
+
+    let cover = tag.and_then(|tag| tag.pictures().first().map(|picture| picture.data().to_vec()));
+    let tidal_id = tag.and_then(tagging::read_tidal_id);
+
+    Ok((song, cover, tidal_id))
+}
+
+//reads the file and adds it to the library, its cover is stored in the cache
+pub fn import_song(app:&Arc<AppImpl>, path:&Path) -> Result<Song, lofty::LoftyError> {
+    let (mut song, cover, _) = read_song(path)?;
+
+    if let Some(cover) = cover {
+        let mut cache_manager = app.cache_manager.blocking_lock();
+        if let Ok(cover_id) = cache_manager.add((&song.artist, &song.album), cover) {
+            song.cover = Some(cover_id.to_string());
+        }
+    }
+
+    let _ = app.database().songs().add_song(song.clone());
+
+    Ok(song)
+}
+
+pub struct LibraryScanner {
+    progress:Arc<Mutex<ScanProgress>>
+}
+
+impl LibraryScanner {
+    pub fn new() -> Self {
+        LibraryScanner {
+            progress: Arc::new(Mutex::new(ScanProgress::default()))
+        }
+    }
+
+    pub fn progress(&self) -> ScanProgress {
+        self.progress.lock().unwrap().clone()
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.progress.lock().unwrap().status == ScanStatus::Scanning
+    }
+
+    pub fn scan(&self, app:Arc<AppImpl>, folders:Vec<PathBuf>) {
+        if self.is_scanning() {
+            return;
+        }
+
+        if let Some(folder) = folders.iter().find(|folder| !folder.is_dir()) {
+            *self.progress.lock().unwrap() = ScanProgress {
+                status: ScanStatus::Failed(format!("{} is not a folder", folder.display())),
+                ..Default::default()
+            };
+
+            return;
+        }
+
+        *self.progress.lock().unwrap() = ScanProgress {
+            status: ScanStatus::Scanning,
+            ..Default::default()
+        };
+
+        let progress = self.progress.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let files = folders.iter().flat_map(|folder| find_audio_files(folder)).collect::<Vec<PathBuf>>();
+
+            {
+                progress.lock().unwrap().total = files.len();
+            }
+
+            //songs that are already in the library are skipped so a folder can be scanned again
+            let known_paths = {
+                app.database().songs().get_songs().into_iter().map(|song| song.path).collect::<HashSet<PathBuf>>()
+            };
+
+            for file in files {
+                {
+                    progress.lock().unwrap().current = Some(file.clone());
+                }
+
+                let imported = if known_paths.contains(&file) {
+                    false
+                } else {
+                    match import_song(&app, &file) {
+                        Ok(_) => true,
+                        Err(_) => {
+                            progress.lock().unwrap().failed += 1;
+                            false
+                        }
+                    }
+                };
+
+                let mut progress = progress.lock().unwrap();
+                progress.scanned += 1;
+                if imported {
+                    progress.imported += 1;
+                }
+            }
+
+            let mut progress = progress.lock().unwrap();
+            progress.current = None;
+            progress.status = ScanStatus::Finished;
+        });
+    }
+}
+
+#[derive(Clone)]
+pub struct Relocation {
+    pub song:Song,
+    pub new_path:PathBuf,
+    pub accepted:bool
+}
+
+#[derive(Clone, PartialEq)]
+pub enum AuditStatus {
+    Idle,
+    Checking,
+    Searching,
+    Done
+}
+
+#[derive(Clone)]
+pub struct AuditState {
+    pub status:AuditStatus,
+    pub missing:Vec<Song>,
+    pub relocations:Vec<Relocation>,
+    pub total:usize,
+    pub scanned:usize
+}
+
+impl Default for AuditState {
+    fn default() -> Self {
+        AuditState {
+            status: AuditStatus::Idle,
+            missing: Vec::new(),
+            relocations: Vec::new(),
+            total: 0,
+            scanned: 0
+        }
+    }
+}
+
+pub fn same_tag(a:&str, b:&str) -> bool {
+    simplify(a).trim() == simplify(b).trim()
+}
+
+//the same song encoded twice can be a bit longer or shorter
+pub fn same_duration(a:u64, b:u64) -> bool {
+    a.abs_diff(b) <= 2000
+}
+
+//a candidate matches if title and artist are the same, the album and the duration are only checked when both sides know them
+pub fn is_same_song(song:&Song, candidate:&Song) -> bool {
+    if !same_tag(&song.title, &candidate.title) || !same_tag(&song.artist, &candidate.artist) {
+        return false;
+    }
+
+    if song.album != "Unknown" && candidate.album != "Unknown" && !same_tag(&song.album, &candidate.album) {
+        return false;
+    }
+
+    match (song.duration, candidate.duration) {
+        (Some(a), Some(b)) => same_duration(a, b),
+        _ => true
+    }
+}
+
+fn find_relocation(song:&Song, candidates:&Vec<(Song, Option<u64>)>) -> Option<PathBuf> {
+    //the tidal id is the same whatever the tags say, it is trusted before anything else
+    let tidal_id = song.tidal_track.as_ref().map(|track| track.id as u64);
+    if let Some((candidate, _)) = candidates.iter().find(|(_, candidate_id)| tidal_id.is_some() && *candidate_id == tidal_id) {
+        return Some(candidate.path.clone());
+    }
+
+    let matches = candidates.iter().map(|(candidate, _)| candidate).filter(|candidate| is_same_song(song, candidate)).collect::<Vec<&Song>>();
+
+    //downloaded songs keep their file name when they are moved, prefer it when there are several matches
+    matches.iter()
+        .find(|candidate| candidate.path.file_name() == song.path.file_name())
+        .or(matches.first())
+        .map(|candidate| candidate.path.clone())
+}
+
+pub struct LibraryAudit {
+    state:Arc<Mutex<AuditState>>
+}
+
+impl LibraryAudit {
+    pub fn new() -> Self {
+        LibraryAudit {
+            state: Arc::new(Mutex::new(AuditState::default()))
+        }
+    }
+
+    pub fn state(&self) -> AuditState {
+        self.state.lock().unwrap().clone()
+    }
+
+    pub fn is_busy(&self) -> bool {
+        let status = self.state.lock().unwrap().status.clone();
+        status == AuditStatus::Checking || status == AuditStatus::Searching
+    }
+
+    pub fn find_missing(&self, app:Arc<AppImpl>) {
+        if self.is_busy() {
+            return;
+        }
+
+        *self.state.lock().unwrap() = AuditState {
+            status: AuditStatus::Checking,
+            ..Default::default()
+        };
+
+        let state = self.state.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let missing = app.database().songs().get_songs().into_iter().filter(|song| !song.path.exists()).collect::<Vec<Song>>();
+
+            let mut state = state.lock().unwrap();
+            state.missing = missing;
+            state.status = AuditStatus::Done;
+        });
+    }
+
+    pub fn search(&self, folder:PathBuf) {
+        if self.is_busy() {
+            return;
+        }
+
+        let missing = {
+            let mut state = self.state.lock().unwrap();
+            state.status = AuditStatus::Searching;
+            state.relocations.clear();
+            state.scanned = 0;
+            state.total = 0;
+            state.missing.clone()
+        };
+
+        let state = self.state.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let files = find_audio_files(&folder);
+
+            {
+                state.lock().unwrap().total = files.len();
+            }
+
+            let mut candidates = vec![];
+            for file in files {
+                if let Ok((candidate, _, tidal_id)) = read_song(&file) {
+                    candidates.push((candidate, tidal_id));
+                }
+
+                state.lock().unwrap().scanned += 1;
+            }
+
+            let mut relocations:Vec<Relocation> = vec![];
+            for song in missing {
+                //a file can only be given to one song
+                let candidates = candidates.iter()
+                    .filter(|(candidate, _)| !relocations.iter().any(|relocation| relocation.new_path == candidate.path))
+                    .cloned()
+                    .collect::<Vec<(Song, Option<u64>)>>();
+
+                if let Some(new_path) = find_relocation(&song, &candidates) {
+                    relocations.push(Relocation {
+                        song,
+                        new_path,
+                        accepted: true
+                    });
+                }
+            }
+
+            let mut state = state.lock().unwrap();
+            state.relocations = relocations;
+            state.status = AuditStatus::Done;
+        });
+    }
+
+    pub fn set_accepted(&self, index:usize, accepted:bool) {
+        if let Some(relocation) = self.state.lock().unwrap().relocations.get_mut(index) {
+            relocation.accepted = accepted;
+        }
+    }
+
+    //rewrites the path of every accepted relocation, returns how many songs were relocated
+    pub fn apply(&self, app:Arc<AppImpl>) -> rusqlite::Result<usize> {
+        let mut state = self.state.lock().unwrap();
+
+        let paths = state.relocations.iter()
+            .filter(|relocation| relocation.accepted)
+            .map(|relocation| (relocation.song.clone(), relocation.new_path.clone()))
+            .collect::<Vec<(Song, PathBuf)>>();
+
+        app.database().songs().set_paths(&paths)?;
+
+        state.missing.retain(|song| !paths.iter().any(|(relocated, _)| relocated.id == song.id));
+        state.relocations.clear();
+
+        Ok(paths.len())
+    }
+}
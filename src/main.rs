@@ -16,6 +16,19 @@ pub mod time;
 pub mod cache;
 pub mod playlist;
 pub mod renderer;
+pub mod library;
+pub mod watcher;
+pub mod trash;
+pub mod migration;
+pub mod backup;
+pub mod history;
+pub mod smart_playlist;
+pub mod playlist_file;
+pub mod cover;
+pub mod artist;
+pub mod search_index;
+pub mod tagging;
+pub mod undo;
 
 #[tokio::main]
 async fn main() -> Result<(), eframe::Error> {
@@ -0,0 +1,385 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::{params, Connection, Transaction};
+use tidal_rs::model::Track;
+
+use crate::{backup, database, song::Song};
+
+pub type DatabaseMigration = fn(&Transaction) -> rusqlite::Result<()>;
+pub type ConfigurationMigration = fn(&mut serde_json::Map<String, serde_json::Value>);
+
+//the migration at index N brings localfy.sqlite from version N to version N + 1, only add new ones at the end
+pub const DATABASE_MIGRATIONS: &[DatabaseMigration] = &[
+    create_schema,
+    song_hashes_to_ids,
+    song_details,
+    delete_references_with_songs,
+    play_history,
+    smart_playlists,
+    unique_playlist_ids,
+    playlist_folders,
+    playlist_covers,
+    artists,
+    download_queue,
+    songs_revision,
+    download_queue_albums,
+    album_track_counts,
+    song_codecs
+];
+
+//same for config.json, the version is stored in its "version" field
+pub const CONFIGURATION_MIGRATIONS: &[ConfigurationMigration] = &[
+    default_max_concurrency,
+    default_download_retries
+];
+
+fn to_io_error(e:rusqlite::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e)
+}
+
+pub fn backup_path(path:&Path, suffix:&str) -> PathBuf {
+    let mut file_name = path.file_name().map(|x| x.to_os_string()).unwrap_or_default();
+    file_name.push(format!(".{}", suffix));
+    path.with_file_name(file_name)
+}
+
+pub fn migrate_database(connection:&mut Connection, path:&Path) -> Result<(), std::io::Error> {
+    let version:usize = connection.query_row("PRAGMA user_version", [], |row| row.get::<_, i64>(0)).map_err(to_io_error)? as usize;
+
+    if version > DATABASE_MIGRATIONS.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("the database is at version {} but this version of Localfy only knows version {}", version, DATABASE_MIGRATIONS.len())));
+    }
+
+    if version == DATABASE_MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    //keep a copy of the database as it was before migrating it, a fresh database has nothing worth saving
+    let is_empty = !connection.prepare("SELECT 1 FROM sqlite_master").and_then(|mut statement| statement.exists([])).map_err(to_io_error)?;
+    if !is_empty {
+        let backup = backup_path(path, &format!("v{}.bak", version));
+        if !backup.exists() {
+            backup::write_atomically(&backup, |temporary_path| {
+                connection.execute("VACUUM INTO ?1", params![temporary_path.to_string_lossy()]).map(|_| ()).map_err(to_io_error)
+            })?;
+        }
+    }
+
+    for (index, migration) in DATABASE_MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction().map_err(to_io_error)?;
+        migration(&transaction).map_err(to_io_error)?;
+        transaction.pragma_update(None, "user_version", (index + 1) as i64).map_err(to_io_error)?;
+        transaction.commit().map_err(to_io_error)?;
+    }
+
+    Ok(())
+}
+
+pub fn migrate_configuration(value:&mut serde_json::Value, path:&Path) -> Result<(), std::io::Error> {
+    let configuration = value.as_object_mut().ok_or(std::io::Error::new(std::io::ErrorKind::InvalidData, "config.json is not an object"))?;
+    let version = configuration.get("version").and_then(|version| version.as_u64()).unwrap_or(0) as usize;
+
+    if version > CONFIGURATION_MIGRATIONS.len() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("config.json is at version {} but this version of Localfy only knows version {}", version, CONFIGURATION_MIGRATIONS.len())));
+    }
+
+    if version == CONFIGURATION_MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let backup = backup_path(path, &format!("v{}.bak", version));
+    if !backup.exists() {
+        backup::write_atomically(&backup, |temporary_path| std::fs::copy(path, temporary_path).map(|_| ()))?;
+    }
+
+    for (index, migration) in CONFIGURATION_MIGRATIONS.iter().enumerate().skip(version) {
+        migration(configuration);
+        configuration.insert("version".to_string(), serde_json::Value::from(index + 1));
+    }
+
+    Ok(())
+}
+
+//version 1 : first sqlite schema, songs were keyed by the hash of the whole song
+fn create_schema(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS songs (
+            hash INTEGER PRIMARY KEY,
+            path TEXT NOT NULL,
+            title TEXT NOT NULL,
+            artist TEXT NOT NULL,
+            album TEXT NOT NULL,
+            tidal_id INTEGER,
+            tidal_track TEXT
+        );
+        CREATE INDEX IF NOT EXISTS songs_tidal_id ON songs(tidal_id);
+
+        CREATE TABLE IF NOT EXISTS playlists (
+            key INTEGER PRIMARY KEY AUTOINCREMENT,
+            id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            image TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS playlist_songs (
+            playlist INTEGER NOT NULL REFERENCES playlists(key) ON DELETE CASCADE,
+            song INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (playlist, song)
+        );
+
+        CREATE TABLE IF NOT EXISTS albums (
+            id INTEGER PRIMARY KEY,
+            data TEXT NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS album_tracks (
+            album INTEGER NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            song INTEGER NOT NULL,
+            PRIMARY KEY (album, position)
+        );
+    ")
+}
+
+//version 2 : songs have a stable id, every reference to a hash is rewritten
+fn song_hashes_to_ids(transaction:&Transaction) -> rusqlite::Result<()> {
+    let songs = {
+        let mut statement = transaction.prepare("SELECT hash, tidal_track FROM songs")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?)))?;
+        rows.collect::<rusqlite::Result<Vec<(i64, Option<String>)>>>()?
+    };
+
+    transaction.execute_batch("CREATE TEMP TABLE song_ids (hash INTEGER PRIMARY KEY, id TEXT NOT NULL);")?;
+    for (hash, tidal_track) in songs {
+        let tidal_track = tidal_track.and_then(|json| serde_json::from_str::<Track>(&json).ok());
+        transaction.execute("INSERT INTO temp.song_ids (hash, id) VALUES (?1, ?2)", params![hash, Song::generate_id(tidal_track.as_ref())])?;
+    }
+
+    transaction.execute_batch("
+        CREATE TABLE songs_v2 (
+            id TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            title TEXT NOT NULL,
+            artist TEXT NOT NULL,
+            album TEXT NOT NULL,
+            tidal_id INTEGER,
+            tidal_track TEXT
+        );
+        INSERT OR REPLACE INTO songs_v2 SELECT song_ids.id, path, title, artist, album, tidal_id, tidal_track FROM songs JOIN temp.song_ids USING (hash);
+        DROP TABLE songs;
+        ALTER TABLE songs_v2 RENAME TO songs;
+        CREATE INDEX IF NOT EXISTS songs_tidal_id ON songs(tidal_id);
+
+        CREATE TABLE playlist_songs_v2 (
+            playlist INTEGER NOT NULL REFERENCES playlists(key) ON DELETE CASCADE,
+            song TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (playlist, song)
+        );
+        INSERT OR IGNORE INTO playlist_songs_v2 SELECT playlist, song_ids.id, position FROM playlist_songs JOIN temp.song_ids ON song_ids.hash = playlist_songs.song;
+        DROP TABLE playlist_songs;
+        ALTER TABLE playlist_songs_v2 RENAME TO playlist_songs;
+
+        CREATE TABLE album_tracks_v2 (
+            album INTEGER NOT NULL REFERENCES albums(id) ON DELETE CASCADE,
+            position INTEGER NOT NULL,
+            song TEXT NOT NULL,
+            PRIMARY KEY (album, position)
+        );
+        INSERT INTO album_tracks_v2 SELECT album, position, song_ids.id FROM album_tracks JOIN temp.song_ids ON song_ids.hash = album_tracks.song;
+        DROP TABLE album_tracks;
+        ALTER TABLE album_tracks_v2 RENAME TO album_tracks;
+
+        DROP TABLE temp.song_ids;
+    ")
+}
+
+//version 3 : track number, duration and cover of songs imported from local files
+fn song_details(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        ALTER TABLE songs ADD COLUMN track_number INTEGER;
+        ALTER TABLE songs ADD COLUMN duration INTEGER;
+        ALTER TABLE songs ADD COLUMN cover TEXT;
+    ")
+}
+
+//version 4 : deleting a song deletes its playlist and album entries, deleted files go to the trash
+fn delete_references_with_songs(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        DELETE FROM playlist_songs WHERE song NOT IN (SELECT id FROM songs);
+        DELETE FROM album_tracks WHERE song NOT IN (SELECT id FROM songs);
+
+        CREATE TRIGGER IF NOT EXISTS songs_delete_references AFTER DELETE ON songs
+        BEGIN
+            DELETE FROM playlist_songs WHERE song = OLD.id;
+            DELETE FROM album_tracks WHERE song = OLD.id;
+        END;
+
+        CREATE TABLE IF NOT EXISTS trash (
+            id TEXT PRIMARY KEY,
+            song TEXT NOT NULL,
+            trash_path TEXT NOT NULL,
+            deleted_at INTEGER NOT NULL,
+            playlists TEXT NOT NULL,
+            albums TEXT NOT NULL
+        );
+    ")
+}
+
+//version 5 : every start, skip and completion of a song, kept when the song is removed so restoring it keeps its history
+fn play_history(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS play_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            song TEXT NOT NULL,
+            event TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            listened INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS play_history_song ON play_history(song, event);
+    ")
+}
+
+//version 6 : songs remember when they were added, playlists with rules are evaluated against the library instead of storing songs
+fn smart_playlists(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        ALTER TABLE songs ADD COLUMN added_at INTEGER;
+        ALTER TABLE playlists ADD COLUMN rules TEXT;
+    ")
+}
+
+//version 7 : playlists are found by their id instead of their name, ids created in the same second used to collide
+fn unique_playlist_ids(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        UPDATE playlists SET id = id || '-' || key WHERE EXISTS (SELECT 1 FROM playlists AS other WHERE other.id = playlists.id AND other.key < playlists.key);
+        CREATE UNIQUE INDEX IF NOT EXISTS playlists_id ON playlists(id);
+        ALTER TABLE playlists ADD COLUMN description TEXT;
+    ")
+}
+
+//version 8 : playlists can be sorted into folders, deleting a folder puts its playlists back at the root
+fn playlist_folders(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS playlist_folders (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            parent TEXT REFERENCES playlist_folders(id) ON DELETE CASCADE,
+            expanded INTEGER NOT NULL DEFAULT 1
+        );
+        ALTER TABLE playlists ADD COLUMN folder TEXT REFERENCES playlist_folders(id) ON DELETE SET NULL;
+    ")
+}
+
+//version 9 : cache id of the mosaic generated from the album covers of a playlist, image stays the cover picked by the user
+fn playlist_covers(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        ALTER TABLE playlists ADD COLUMN cover TEXT;
+    ")
+}
+
+//version 10 : artists are entities of the library, every song points to its artist
+fn artists(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS artists (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            tidal_id INTEGER,
+            picture TEXT
+        );
+        ALTER TABLE songs ADD COLUMN artist_id TEXT;
+        CREATE INDEX IF NOT EXISTS songs_artist_id ON songs(artist_id);
+    ")?;
+
+    //songs from tidal first so local songs of the same artist are attached to the tidal one
+    let songs = {
+        let mut statement = transaction.prepare("SELECT id, artist, tidal_track FROM songs ORDER BY tidal_track IS NULL")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?)))?;
+        rows.collect::<rusqlite::Result<Vec<(String, String, Option<String>)>>>()?
+    };
+
+    for (id, artist, tidal_track) in songs {
+        let tidal_track = tidal_track.and_then(|json| serde_json::from_str::<Track>(&json).ok());
+        let artist_id = database::upsert_artist(transaction, &artist, tidal_track.as_ref())?;
+        transaction.execute("UPDATE songs SET artist_id = ?1 WHERE id = ?2", params![artist_id, id])?;
+    }
+
+    Ok(())
+}
+
+//version 11 : downloads that are still queued are kept so they continue after a restart
+fn download_queue(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS download_queue (
+            key INTEGER PRIMARY KEY AUTOINCREMENT,
+            track_id INTEGER NOT NULL UNIQUE,
+            track TEXT NOT NULL,
+            quality TEXT NOT NULL,
+            playlist TEXT
+        );
+    ")
+}
+
+//version 12 : a counter of the changes made to the songs only, so writes to the other tables don't rebuild the search index
+fn songs_revision(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE IF NOT EXISTS songs_revision (
+            id INTEGER PRIMARY KEY CHECK (id = 0),
+            value INTEGER NOT NULL
+        );
+        INSERT OR IGNORE INTO songs_revision (id, value) VALUES (0, 0);
+
+        CREATE TRIGGER IF NOT EXISTS songs_revision_insert AFTER INSERT ON songs BEGIN
+            UPDATE songs_revision SET value = value + 1;
+        END;
+        CREATE TRIGGER IF NOT EXISTS songs_revision_update AFTER UPDATE ON songs BEGIN
+            UPDATE songs_revision SET value = value + 1;
+        END;
+        CREATE TRIGGER IF NOT EXISTS songs_revision_delete AFTER DELETE ON songs BEGIN
+            UPDATE songs_revision SET value = value + 1;
+        END;
+    ")
+}
+
+//version 13 : the album a queued track belongs to, its tracks stay until the last one is done so the album can be added, and whether the download was paused
+fn download_queue_albums(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        ALTER TABLE download_queue ADD COLUMN album_id INTEGER;
+        ALTER TABLE download_queue ADD COLUMN album TEXT;
+        ALTER TABLE download_queue ADD COLUMN paused INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE download_queue ADD COLUMN finished INTEGER NOT NULL DEFAULT 0;
+    ")
+}
+
+//version 14 : how many tracks tidal lists for an album, the library may only have some of them
+fn album_track_counts(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        CREATE TABLE album_track_counts (
+            album INTEGER PRIMARY KEY,
+            track_count INTEGER NOT NULL
+        );
+    ")
+}
+
+//version 15 : whether the codec of a song is lossless, read from the file since an m4a can hold aac or alac
+fn song_codecs(transaction:&Transaction) -> rusqlite::Result<()> {
+    transaction.execute_batch("
+        ALTER TABLE songs ADD COLUMN lossless INTEGER;
+    ")
+}
+
+//version 1 : max_concurrency used to default to 0 when it was missing, which meant no download ever started
+fn default_max_concurrency(configuration:&mut serde_json::Map<String, serde_json::Value>) {
+    let max_concurrency = configuration.get("max_concurrency").and_then(|x| x.as_u64()).unwrap_or(0);
+
+    if max_concurrency == 0 {
+        configuration.insert("max_concurrency".to_string(), serde_json::Value::from(10));
+    }
+}
+
+//version 2 : failed downloads are retried, existing configurations get the same number of retries as new ones
+fn default_download_retries(configuration:&mut serde_json::Map<String, serde_json::Value>) {
+    if !configuration.contains_key("download_retries") {
+        configuration.insert("download_retries".to_string(), serde_json::Value::from(3));
+    }
+}
@@ -2,7 +2,7 @@ use std::{sync::{Arc, Mutex}, ops::Deref, collections::VecDeque};
 
 use rand::seq::SliceRandom;
 use vlc::{Instance, MediaPlayer, Media};
-use crate::song::Song;
+use crate::{history::{ListeningSession, PlayEvent, PlayEventKind}, song::Song};
 use vlc::MediaPlayerAudioEx;
 
 pub struct PlayerQueue {
@@ -162,6 +162,8 @@ pub struct PlayerImpl {
     pub media_player: vlc::MediaPlayer,
     pub per_song_gui_settings:Mutex<PerSongGuiSettings>,
     pub queue:Mutex<PlayerQueue>,
+    pub listening_session:Mutex<Option<ListeningSession>>,
+    pub play_events:Mutex<Vec<PlayEvent>>, //waiting to be written to the database
     pub event_manager:(std::sync::mpsc::Sender<vlc::EventType>, std::sync::mpsc::Receiver<vlc::EventType>)
 }
 
@@ -176,6 +178,8 @@ impl PlayerImpl {
             media_player: media_player,
             per_song_gui_settings: Mutex::new(PerSongGuiSettings::default()),
             queue: Mutex::new(PlayerQueue::default()),
+            listening_session: Mutex::new(None),
+            play_events: Mutex::new(Vec::new()),
             event_manager
         };
 
@@ -189,9 +193,14 @@ impl PlayerImpl {
     }
 
     pub fn tick(&self) {
+        if let Some(session) = self.listening_session.lock().unwrap().as_mut() {
+            session.tick(self.is_playing());
+        }
+
         if let Ok(event) = self.event_manager.1.try_recv() {
             match event {
                 vlc::EventType::MediaPlayerEndReached => {
+                    self.end_listening_session(PlayEventKind::Complete);
 
                     let song = {
                         self.queue.lock().unwrap().get_next_song()
@@ -206,6 +215,25 @@ impl PlayerImpl {
         }
     }
 
+    fn end_listening_session(&self, kind:PlayEventKind) {
+        if let Some(session) = self.listening_session.lock().unwrap().take() {
+            self.play_events.lock().unwrap().push(session.to_event(kind));
+        }
+    }
+
+    //a song that is still being listened to when another one starts was skipped
+    fn start_listening_session(&self, song:&Song) {
+        self.end_listening_session(PlayEventKind::Skip);
+
+        let session = ListeningSession::new(song);
+        self.play_events.lock().unwrap().push(session.to_event(PlayEventKind::Start));
+        *self.listening_session.lock().unwrap() = Some(session);
+    }
+
+    pub fn take_play_events(&self) -> Vec<PlayEvent> {
+        std::mem::take(&mut *self.play_events.lock().unwrap())
+    }
+
     pub fn set_playback_mode(&self, playback_mode:PlaybackMode) {
         self.queue().playback_mode = playback_mode;
     }
@@ -255,6 +283,7 @@ impl PlayerImpl {
 
     pub fn stop(&self) {
         self.media_player.stop();
+        self.end_listening_session(PlayEventKind::Skip);
     }
 
     pub fn set_position(&self, position:f32) {
@@ -294,6 +323,7 @@ impl PlayerImpl {
         self.media_player.set_media(&media);
 
         *self.per_song_gui_settings.lock().unwrap() = PerSongGuiSettings::default();
+        self.start_listening_session(song);
         self.play();
 
         {
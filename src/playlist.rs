@@ -1,8 +1,8 @@
 use std::{path::PathBuf, sync::Arc};
 
-use egui::{pos2, Align2, Color32, FontFamily, FontId, ImageSource, Widget};
+use egui::{pos2, vec2, Align2, Color32, FontFamily, FontId, Image, ImageSource, Rounding, Widget};
 
-use crate::{app::{self, App, AppImpl}, constants::{BACKGROUND_COLOR, TEXT_COLOR}, gui::{model::Pages, page::RenderablePage, song::{SongWidget}}, song::Song};
+use crate::{app::{self, App, AppImpl}, constants::{BACKGROUND_COLOR, SELECTION_COLOR, TEXT_COLOR}, cover, gui::{components::rules_editor::rules_editor, model::Pages, page::RenderablePage, song::{SongWidget}}, playlist_file::{self, PlaylistFormat}, song::Song, smart_playlist::SmartRules, undo::{self, Command}};
 
 
 
@@ -11,13 +11,27 @@ pub struct PlaylistDescriptor {
     pub id:String,
     pub name:String,
     pub image:Option<PathBuf>,
+    #[serde(default)]
+    pub description:Option<String>,
+    #[serde(default)]
+    pub smart:bool,
+    #[serde(default)]
+    pub folder:Option<String>, //id of the folder the playlist is in, None at the root
+    #[serde(default)]
+    pub cover:Option<String> //id of the generated mosaic in the cache, None until it is generated or when no song has a cover
+}
+
+#[derive(Clone, Debug, Hash, PartialEq)]
+pub struct PlaylistFolder {
+    pub id:String,
+    pub name:String,
+    pub parent:Option<String>,
+    pub expanded:bool
 }
 
 impl PlaylistDescriptor {
-    pub fn hash_songs(&self, app:Arc<AppImpl>) -> Vec<u64> {
-        {
-            app.database().raw().data.lock().unwrap().playlists.iter().find(|x| x.name == self.name).unwrap_or(&Playlist { id: self.id.clone(), name: self.name.clone(), image: self.image.clone(), songs: vec![] }).songs.clone()
-        }
+    pub fn song_ids(&self, app:Arc<AppImpl>) -> Vec<String> {
+        app.database().playlists().get_playlist_song_ids(self)
     }
 }
 
@@ -26,7 +40,15 @@ pub struct Playlist {
     pub id:String,
     pub name:String,
     pub image:Option<PathBuf>,
-    pub(crate) songs:Vec<u64> // Vec to hashes of songs to gain performance and reduce database size
+    pub description:Option<String>,
+    pub(crate) songs:Vec<String>, // ids of the songs
+    pub rules:Option<SmartRules> // smart playlists are filled with the songs matching their rules instead
+}
+
+impl Playlist {
+    pub fn generate_id() -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
 }
 
 impl From<Playlist> for PlaylistDescriptor {
@@ -34,7 +56,11 @@ impl From<Playlist> for PlaylistDescriptor {
         Self {
             id:playlist.id,
             name:playlist.name,
-            image:playlist.image
+            image:playlist.image,
+            description:playlist.description,
+            smart:playlist.rules.is_some(),
+            folder:None,
+            cover:None
         }
     }
 }
@@ -57,6 +83,11 @@ impl RenderablePage for PlaylistDescriptor {
             application.gui_settings.page = Pages::Home;
         }
 
+        //the descriptor of the page can be outdated after a rename
+        let descriptor = {
+            application.app.database().playlists().get_playlist(&self.id)
+        }.unwrap_or(self.clone());
+
         let list_rect = max_rect.expand2(egui::vec2(0., -50.)).shrink(35.);
         let paint_rect = list_rect.expand(10.0);
 
@@ -64,9 +95,130 @@ impl RenderablePage for PlaylistDescriptor {
         ui.painter().rect_filled(paint_rect, 10.0, BACKGROUND_COLOR);
 
         let font = FontId::new(17., FontFamily::Name("Montserrat".into()));
-        ui.painter().text(pos2(paint_rect.min.x, paint_rect.min.y - 35.0), Align2::LEFT_CENTER, &self.name, font, TEXT_COLOR);
+        ui.painter().text(pos2(paint_rect.min.x, paint_rect.min.y - 35.0), Align2::LEFT_CENTER, &descriptor.name, font, TEXT_COLOR);
         ui.painter().text(pos2(paint_rect.min.x, paint_rect.min.y - 10.0), Align2::LEFT_CENTER, "Playlist - WorkInProgress", FontId::proportional(14.), Color32::RED);
 
+        let edit_playlist = egui::Id::new(("_edit_playlist_", &self.id));
+
+        cover::request_mosaic(&application.app, &descriptor);
+
+        ui.horizontal(|ui| {
+            ui.add(Image::new(descriptor.get_page_icon()).fit_to_exact_size(vec2(60., 60.)).rounding(Rounding::same(15.)));
+
+            if ui.button("Edit").clicked() {
+                application.gui_settings.playlist_name_edit = descriptor.name.clone();
+                application.gui_settings.playlist_description_edit = descriptor.description.clone().unwrap_or_default();
+                ui.memory_mut(|mem| mem.data.insert_temp(edit_playlist, true));
+            }
+
+            if ui.button("Duplicate").clicked() {
+                let duplicate = {
+                    application.app.database().playlists().duplicate_playlist(&descriptor, &format!("{} (copy)", descriptor.name))
+                };
+
+                if let Ok(duplicate) = duplicate {
+                    application.gui_settings.location = crate::gui::model::UserLocation::Playlist(duplicate);
+                }
+            }
+
+            if let Some(description) = &descriptor.description {
+                ui.label(description);
+            }
+
+            ui.menu_button("Cover", |ui| {
+                if ui.button("Choose image").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().add_filter("Image", &["png", "jpg", "jpeg", "webp"]).pick_file() {
+                        let _ = application.app.database().playlists().set_image(&descriptor, Some(&path));
+                    }
+
+                    ui.close_menu();
+                }
+
+                if descriptor.image.is_some() && ui.button("Use generated cover").clicked() {
+                    let _ = application.app.database().playlists().set_image(&descriptor, None);
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button("Export", |ui| {
+                ui.checkbox(&mut application.gui_settings.export_relative_paths, "Relative paths");
+
+                for format in PlaylistFormat::ALL {
+                    if ui.button(format.to_string()).clicked() {
+                        let destination = rfd::FileDialog::new()
+                            .set_file_name(format!("{}.{}", descriptor.name, format.extension()))
+                            .add_filter(format.to_string(), &[format.extension()])
+                            .save_file();
+
+                        let playlist = {
+                            application.app.database().playlists().unhash_playlist_songs(self)
+                        };
+
+                        if let (Some(destination), Some(playlist)) = (destination, playlist) {
+                            if let Err(e) = playlist_file::export(&playlist, &destination, format, application.gui_settings.export_relative_paths) {
+                                println!("Failed to export playlist : {}", e);
+                            }
+                        }
+
+                        ui.close_menu();
+                    }
+                }
+            });
+        });
+
+        if ui.memory(|mem| mem.data.get_temp(edit_playlist).unwrap_or(false)) {
+            egui::Window::new("Edit playlist").show(ui.ctx(), |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Name");
+                    ui.text_edit_singleline(&mut application.gui_settings.playlist_name_edit);
+                });
+
+                ui.label("Description");
+                ui.text_edit_multiline(&mut application.gui_settings.playlist_description_edit);
+
+                ui.horizontal(|ui| {
+                    if !application.gui_settings.playlist_name_edit.is_empty() && ui.button("Save").clicked() {
+                        let description = Some(application.gui_settings.playlist_description_edit.trim().to_string()).filter(|x| !x.is_empty());
+
+                        let command = Command::edit_playlist(&descriptor, application.gui_settings.playlist_name_edit.clone(), description);
+                        if let Err(e) = undo::execute(&application.app, command) {
+                            println!("Failed to edit playlist : {}", e);
+                        }
+
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(edit_playlist));
+                    }
+
+                    if ui.button("Cancel").clicked() {
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(edit_playlist));
+                    }
+                });
+            });
+        }
+
+        if self.smart {
+            let edit_rules = egui::Id::new(("_edit_rules_", &self.id));
+
+            if ui.button("Edit rules").clicked() {
+                ui.memory_mut(|mem| mem.data.insert_temp(edit_rules, true));
+            }
+
+            let rules = {
+                application.app.database().playlists().get_rules(self)
+            };
+
+            if let Some(mut rules) = rules.filter(|_| ui.memory(|mem| mem.data.get_temp(edit_rules).unwrap_or(false))) {
+                egui::Window::new("Smart playlist rules").show(ui.ctx(), |ui| {
+                    if rules_editor(ui, &mut rules) {
+                        let _ = application.app.database().playlists().set_rules(self, &rules);
+                    }
+
+                    if ui.button("Close").clicked() {
+                        ui.memory_mut(|mem| mem.data.remove::<bool>(edit_rules));
+                    }
+                });
+            }
+        }
+
         //create padding
         let mut container = ui.child_ui(list_rect, egui::Layout::default());
 
@@ -77,11 +229,22 @@ impl RenderablePage for PlaylistDescriptor {
         if let Some(resolved_playlist) = songs {
             egui::ScrollArea::new([false, true]).show(&mut container, |container: &mut egui::Ui| {
             
-                resolved_playlist.songs.iter().for_each(|song| {
-                    let response = container.add(SongWidget::new(song.clone()));
+                let list = format!("playlist:{}", self.id);
+
+                resolved_playlist.songs.iter().enumerate().for_each(|(index, song)| {
+                    let widget = if application.gui_settings.selection.is_selected(&list, song) {
+                        SongWidget::new(song.clone()).background_color(SELECTION_COLOR)
+                    } else {
+                        SongWidget::new(song.clone())
+                    };
+
+                    let response = container.add(widget);
 
                     if response.clicked() {
-                        song.on_clicked(application.app.clone(), crate::gui::model::UserLocation::Playlist(resolved_playlist.descriptor.clone()));
+                        let modifiers = container.input(|input| input.modifiers);
+                        if !application.gui_settings.selection.handle_click(&list, &resolved_playlist.songs, index, modifiers) {
+                            song.on_clicked(application.app.clone(), crate::gui::model::UserLocation::Playlist(resolved_playlist.descriptor.clone()));
+                        }
                     }
 
                     song.context_menu(response, ui, application);
@@ -95,6 +258,6 @@ impl RenderablePage for PlaylistDescriptor {
     }
 
     fn get_page_icon(&self) -> ImageSource {
-        todo!()
+        cover::playlist_cover(self)
     }
 }
\ No newline at end of file
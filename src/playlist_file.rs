@@ -0,0 +1,604 @@
+use std::{collections::HashMap, path::{Component, Path, PathBuf}, sync::Arc};
+
+use crate::{app::AppImpl, library, playlist::{DecodedPlaylist, Playlist}, song::Song};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Pls,
+    Xspf
+}
+
+impl PlaylistFormat {
+    pub const ALL: [PlaylistFormat; 3] = [PlaylistFormat::M3u8, PlaylistFormat::Pls, PlaylistFormat::Xspf];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PlaylistFormat::M3u8 => "m3u8",
+            PlaylistFormat::Pls => "pls",
+            PlaylistFormat::Xspf => "xspf"
+        }
+    }
+
+    pub fn from_path(path:&Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "m3u8" | "m3u" => Some(PlaylistFormat::M3u8),
+            "pls" => Some(PlaylistFormat::Pls),
+            "xspf" => Some(PlaylistFormat::Xspf),
+            _ => None
+        }
+    }
+}
+
+impl ToString for PlaylistFormat {
+    fn to_string(&self) -> String {
+        match self {
+            PlaylistFormat::M3u8 => "M3U8".to_string(),
+            PlaylistFormat::Pls => "PLS".to_string(),
+            PlaylistFormat::Xspf => "XSPF".to_string()
+        }
+    }
+}
+
+//one line of a playlist file, nothing is guaranteed to be there
+#[derive(Clone, Debug, Default)]
+pub struct PlaylistEntry {
+    pub path:Option<PathBuf>,
+    pub title:Option<String>,
+    pub artist:Option<String>,
+    pub duration:Option<u64> //ms
+}
+
+impl ToString for PlaylistEntry {
+    fn to_string(&self) -> String {
+        match (&self.artist, &self.title, &self.path) {
+            (Some(artist), Some(title), _) => format!("{} - {}", artist, title),
+            (None, Some(title), _) => title.clone(),
+            (_, _, Some(path)) => path.display().to_string(),
+            _ => "Unknown entry".to_string()
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportReport {
+    pub playlist_name:String,
+    pub matched:usize,
+    pub unresolved:Vec<String>
+}
+
+//path of `path` seen from the folder `base`, None when they don't share a root (another drive)
+fn relative_path(base:&Path, path:&Path) -> Option<PathBuf> {
+    let base = base.components().collect::<Vec<Component>>();
+    let path = path.components().collect::<Vec<Component>>();
+
+    if base.first() != path.first() {
+        return None;
+    }
+
+    let common = base.iter().zip(path.iter()).take_while(|(a, b)| a == b).count();
+
+    let mut result = PathBuf::new();
+    for _ in common..base.len() {
+        result.push("..");
+    }
+
+    for component in &path[common..] {
+        result.push(component);
+    }
+
+    Some(result)
+}
+
+fn entry_path(song:&Song, base:&Path, relative:bool) -> PathBuf {
+    if relative {
+        if let Some(path) = relative_path(base, &song.path) {
+            return path;
+        }
+    }
+
+    song.path.clone()
+}
+
+fn escape_xml(text:&str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(text:&str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn encode_uri(path:&Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    path.bytes().map(|byte| {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) {
+            (byte as char).to_string()
+        } else {
+            format!("%{:02X}", byte)
+        }
+    }).collect()
+}
+
+fn decode_uri(uri:&str) -> PathBuf {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or_default();
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&decoded).to_string())
+}
+
+fn location_to_path(location:&str, base:&Path) -> PathBuf {
+    let location = location.trim();
+
+    let path = if let Some(uri) = location.strip_prefix("file://") {
+        let path = decode_uri(uri);
+
+        //file:///C:/Music on windows
+        let text = path.to_string_lossy().to_string();
+        match text.strip_prefix('/') {
+            Some(rest) if rest.chars().nth(1) == Some(':') => PathBuf::from(rest),
+            _ => path
+        }
+    } else {
+        PathBuf::from(location)
+    };
+
+    if path.is_relative() {
+        base.join(path)
+    } else {
+        path
+    }
+}
+
+pub fn write_m3u8(playlist:&DecodedPlaylist, base:&Path, relative:bool) -> String {
+    let mut content = format!("#EXTM3U\n#PLAYLIST:{}\n", playlist.descriptor.name);
+
+    for song in &playlist.songs {
+        let duration = song.duration.map(|duration| (duration / 1000) as i64).unwrap_or(-1);
+        content.push_str(&format!("#EXTINF:{},{} - {}\n", duration, song.artist, song.title));
+        content.push_str(&format!("{}\n", entry_path(song, base, relative).display()));
+    }
+
+    content
+}
+
+pub fn write_pls(playlist:&DecodedPlaylist, base:&Path, relative:bool) -> String {
+    let mut content = String::from("[playlist]\n");
+
+    for (index, song) in playlist.songs.iter().enumerate() {
+        let number = index + 1;
+        let duration = song.duration.map(|duration| (duration / 1000) as i64).unwrap_or(-1);
+
+        content.push_str(&format!("File{}={}\n", number, entry_path(song, base, relative).display()));
+        content.push_str(&format!("Title{}={} - {}\n", number, song.artist, song.title));
+        content.push_str(&format!("Length{}={}\n", number, duration));
+    }
+
+    content.push_str(&format!("NumberOfEntries={}\nVersion=2\n", playlist.songs.len()));
+    content
+}
+
+pub fn write_xspf(playlist:&DecodedPlaylist, base:&Path, relative:bool) -> String {
+    let mut content = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+    content.push_str(&format!("  <title>{}</title>\n  <trackList>\n", escape_xml(&playlist.descriptor.name)));
+
+    for song in &playlist.songs {
+        let path = entry_path(song, base, relative);
+        let location = if path.is_absolute() {
+            format!("file:///{}", encode_uri(&path).trim_start_matches('/'))
+        } else {
+            encode_uri(&path)
+        };
+
+        content.push_str("    <track>\n");
+        content.push_str(&format!("      <location>{}</location>\n", escape_xml(&location)));
+        content.push_str(&format!("      <title>{}</title>\n", escape_xml(&song.title)));
+        content.push_str(&format!("      <creator>{}</creator>\n", escape_xml(&song.artist)));
+        content.push_str(&format!("      <album>{}</album>\n", escape_xml(&song.album)));
+        if let Some(duration) = song.duration {
+            content.push_str(&format!("      <duration>{}</duration>\n", duration));
+        }
+        content.push_str("    </track>\n");
+    }
+
+    content.push_str("  </trackList>\n</playlist>\n");
+    content
+}
+
+//"Artist - Title" is what most players write, anything else is kept as the title
+fn split_display_title(text:&str) -> (Option<String>, Option<String>) {
+    let text = text.trim();
+    if text.is_empty() {
+        return (None, None);
+    }
+
+    match text.split_once(" - ") {
+        Some((artist, title)) => (Some(artist.trim().to_string()), Some(title.trim().to_string())),
+        None => (None, Some(text.to_string()))
+    }
+}
+
+fn parse_seconds(text:&str) -> Option<u64> {
+    text.trim().parse::<i64>().ok().filter(|secs| *secs >= 0).map(|secs| secs as u64 * 1000)
+}
+
+pub fn read_m3u8(content:&str, base:&Path) -> (Option<String>, Vec<PlaylistEntry>) {
+    let mut name = None;
+    let mut entries = vec![];
+    let mut current = PlaylistEntry::default();
+
+    for line in content.lines().map(|line| line.trim_start_matches('\u{feff}').trim()) {
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(playlist_name) = line.strip_prefix("#PLAYLIST:") {
+            name = Some(playlist_name.trim().to_string());
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, display_title) = info.split_once(',').unwrap_or((info, ""));
+            let (artist, title) = split_display_title(display_title);
+
+            current.duration = parse_seconds(duration);
+            current.artist = artist;
+            current.title = title;
+        } else if !line.starts_with('#') {
+            current.path = Some(location_to_path(line, base));
+            entries.push(std::mem::take(&mut current));
+        }
+    }
+
+    (name, entries)
+}
+
+pub fn read_pls(content:&str, base:&Path) -> (Option<String>, Vec<PlaylistEntry>) {
+    let mut entries:HashMap<usize, PlaylistEntry> = HashMap::new();
+
+    for line in content.lines().map(|line| line.trim()) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let key = key.trim().to_lowercase();
+        let (field, number) = key.split_at(key.find(|c:char| c.is_ascii_digit()).unwrap_or(key.len()));
+        let Ok(number) = number.parse::<usize>() else {
+            continue;
+        };
+
+        let entry = entries.entry(number).or_default();
+        match field {
+            "file" => entry.path = Some(location_to_path(value, base)),
+            "title" => {
+                let (artist, title) = split_display_title(value);
+                entry.artist = artist;
+                entry.title = title;
+            },
+            "length" => entry.duration = parse_seconds(value),
+            _ => {}
+        }
+    }
+
+    let mut numbers = entries.keys().cloned().collect::<Vec<usize>>();
+    numbers.sort();
+
+    (None, numbers.into_iter().filter_map(|number| entries.remove(&number)).filter(|entry| entry.path.is_some()).collect())
+}
+
+//text of the first <tag>...</tag> found in the xml
+fn xml_value(xml:&str, tag:&str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", tag))?;
+
+    Some(unescape_xml(xml[start..end].trim()))
+}
+
+pub fn read_xspf(content:&str, base:&Path) -> (Option<String>, Vec<PlaylistEntry>) {
+    let track_list_start = content.find("<trackList>").unwrap_or(0);
+    let name = xml_value(&content[..track_list_start], "title");
+
+    let entries = content[track_list_start..].split("<track>").skip(1).map(|track| {
+        let track = track.split("</track>").next().unwrap_or_default();
+
+        PlaylistEntry {
+            //relative locations are uris too
+            path: xml_value(track, "location").map(|location| if location.starts_with("file://") {
+                location_to_path(&location, base)
+            } else {
+                location_to_path(&decode_uri(&location).to_string_lossy(), base)
+            }),
+            title: xml_value(track, "title"),
+            artist: xml_value(track, "creator"),
+            duration: xml_value(track, "duration").and_then(|duration| duration.parse::<u64>().ok())
+        }
+    }).collect();
+
+    (name, entries)
+}
+
+pub fn export(playlist:&DecodedPlaylist, path:&Path, format:PlaylistFormat, relative:bool) -> std::io::Result<()> {
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let content = match format {
+        PlaylistFormat::M3u8 => write_m3u8(playlist, base, relative),
+        PlaylistFormat::Pls => write_pls(playlist, base, relative),
+        PlaylistFormat::Xspf => write_xspf(playlist, base, relative)
+    };
+
+    std::fs::write(path, content)
+}
+
+//an entry is matched by its path first, then by its tags.
+//a title alone is shared by too many songs, it also needs the duration and has to point to a single song
+fn find_song(entry:&PlaylistEntry, library:&Vec<Song>, songs_by_path:&HashMap<PathBuf, usize>) -> Option<Song> {
+    if let Some(path) = &entry.path {
+        let path = path.canonicalize().unwrap_or(path.clone());
+        if let Some(index) = songs_by_path.get(&path) {
+            return Some(library[*index].clone());
+        }
+    }
+
+    let mut candidate = Song::new(
+        entry.path.clone().unwrap_or_default(),
+        entry.title.clone()?,
+        entry.artist.clone().unwrap_or_default(),
+        "Unknown".to_string()
+    );
+    candidate.duration = entry.duration;
+
+    if entry.artist.is_some() {
+        return library.iter().find(|song| library::is_same_song(song, &candidate)).cloned();
+    }
+
+    let duration = entry.duration?;
+    let mut matches = library.iter().filter(|song| {
+        library::same_tag(&song.title, &candidate.title) && song.duration.is_some_and(|song_duration| library::same_duration(song_duration, duration))
+    });
+
+    match (matches.next(), matches.next()) {
+        (Some(song), None) => Some(song.clone()),
+        _ => None
+    }
+}
+
+//m3u8 is utf-8 by definition, plain m3u files are usually written in latin-1 by older players
+fn decode(path:&Path, bytes:Vec<u8>) -> String {
+    match String::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => {
+            let bytes = e.into_bytes();
+
+            if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("m3u")) {
+                bytes.iter().map(|byte| *byte as char).collect()
+            } else {
+                String::from_utf8_lossy(&bytes).to_string()
+            }
+        }
+    }
+}
+
+//every path of the library is resolved, this is meant to run outside of the gui thread
+pub fn import(app:&Arc<AppImpl>, path:&Path) -> std::io::Result<ImportReport> {
+    let format = PlaylistFormat::from_path(path).ok_or(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Unknown playlist format"))?;
+    let content = decode(path, std::fs::read(path)?);
+    let base = path.parent().unwrap_or(Path::new("."));
+
+    let (name, entries) = match format {
+        PlaylistFormat::M3u8 => read_m3u8(&content, base),
+        PlaylistFormat::Pls => read_pls(&content, base),
+        PlaylistFormat::Xspf => read_xspf(&content, base)
+    };
+
+    let library = {
+        app.database().songs().get_songs()
+    };
+
+    let songs_by_path = library.iter().enumerate()
+        .map(|(index, song)| (song.path.canonicalize().unwrap_or(song.path.clone()), index))
+        .collect::<HashMap<PathBuf, usize>>();
+
+    let mut songs:Vec<String> = vec![];
+    let mut unresolved = vec![];
+
+    for entry in &entries {
+        match find_song(entry, &library, &songs_by_path) {
+            Some(song) => if !songs.contains(&song.id) {
+                songs.push(song.id);
+            },
+            None => unresolved.push(entry.to_string())
+        }
+    }
+
+    let playlist_name = name
+        .filter(|name| !name.is_empty())
+        .or(path.file_stem().map(|x| x.to_string_lossy().to_string()))
+        .unwrap_or("Imported playlist".to_string());
+
+    let matched = songs.len();
+
+    app.database().playlists().add_playlist(&Playlist {
+        id: Playlist::generate_id(),
+        name: playlist_name.clone(),
+        image: None,
+        description: None,
+        songs,
+        rules: None
+    }).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+
+    Ok(ImportReport {
+        playlist_name,
+        matched,
+        unresolved
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playlist::PlaylistDescriptor;
+
+    fn song(path:&str, title:&str, artist:&str, duration:Option<u64>) -> Song {
+        let mut song = Song::new(PathBuf::from(path), title.to_string(), artist.to_string(), "Unknown".to_string());
+        song.duration = duration;
+        song
+    }
+
+    fn playlist(songs:Vec<Song>) -> DecodedPlaylist {
+        DecodedPlaylist {
+            descriptor: PlaylistDescriptor::from(Playlist {
+                id: "1".to_string(),
+                name: "Road & Trip".to_string(),
+                image: None,
+                description: None,
+                songs: vec![],
+                rules: None
+            }),
+            songs
+        }
+    }
+
+    fn sample() -> DecodedPlaylist {
+        playlist(vec![
+            song("/music/Daft Punk/One More Time.flac", "One More Time", "Daft Punk", Some(320_000)),
+            song("/music/Sigur Rós/Hoppípolla & more.mp3", "Hoppípolla", "Sigur Rós", None)
+        ])
+    }
+
+    fn assert_round_trip(playlist:&DecodedPlaylist, entries:&Vec<PlaylistEntry>) {
+        assert_eq!(entries.len(), playlist.songs.len());
+
+        for (song, entry) in playlist.songs.iter().zip(entries) {
+            assert_eq!(entry.path.as_ref(), Some(&song.path));
+            assert_eq!(entry.title.as_deref(), Some(song.title.as_str()));
+            assert_eq!(entry.artist.as_deref(), Some(song.artist.as_str()));
+            assert_eq!(entry.duration, song.duration);
+        }
+    }
+
+    #[test]
+    fn m3u8_round_trip() {
+        let playlist = sample();
+        let (name, entries) = read_m3u8(&write_m3u8(&playlist, Path::new("/playlists"), false), Path::new("/playlists"));
+
+        assert_eq!(name.as_deref(), Some("Road & Trip"));
+        assert_round_trip(&playlist, &entries);
+    }
+
+    #[test]
+    fn pls_round_trip() {
+        let playlist = sample();
+        let (name, entries) = read_pls(&write_pls(&playlist, Path::new("/playlists"), false), Path::new("/playlists"));
+
+        assert_eq!(name, None);
+        assert_round_trip(&playlist, &entries);
+    }
+
+    #[test]
+    fn xspf_round_trip() {
+        let playlist = sample();
+        let (name, entries) = read_xspf(&write_xspf(&playlist, Path::new("/playlists"), false), Path::new("/playlists"));
+
+        assert_eq!(name.as_deref(), Some("Road & Trip"));
+        assert_round_trip(&playlist, &entries);
+    }
+
+    #[test]
+    fn relative_paths_are_resolved_from_the_playlist_folder() {
+        let playlist = sample();
+        let base = Path::new("/music/playlists");
+
+        for content in [write_m3u8(&playlist, base, true), write_pls(&playlist, base, true), write_xspf(&playlist, base, true)] {
+            assert!(content.contains(".."));
+        }
+
+        let (_, entries) = read_xspf(&write_xspf(&playlist, base, true), base);
+        assert_eq!(entries[1].path, Some(base.join("../Sigur Rós/Hoppípolla & more.mp3")));
+    }
+
+    #[test]
+    fn malformed_m3u8() {
+        let content = "\u{feff}#EXTM3U\n#EXTINF:abc,\n\n#EXTVLCOPT:network-caching=1000\nsong.mp3\n#EXTINF:12\n";
+        let (name, entries) = read_m3u8(content, Path::new("/playlists"));
+
+        assert_eq!(name, None);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, Some(PathBuf::from("/playlists/song.mp3")));
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[0].title, None);
+    }
+
+    #[test]
+    fn malformed_pls() {
+        let content = "[playlist]\nFile2=b.mp3\nTitle3=No file\nFile1=a.mp3\nLength1=-1\nnot a key\nFilex=c.mp3\n";
+        let (_, entries) = read_pls(content, Path::new("/playlists"));
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Some(PathBuf::from("/playlists/a.mp3")));
+        assert_eq!(entries[0].duration, None);
+        assert_eq!(entries[1].path, Some(PathBuf::from("/playlists/b.mp3")));
+    }
+
+    #[test]
+    fn malformed_xspf() {
+        let content = "<playlist><trackList><track><location>file:///music/a%20b.mp3</location></track><track><title>Cut";
+        let (name, entries) = read_xspf(content, Path::new("/playlists"));
+
+        assert_eq!(name, None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Some(PathBuf::from("/music/a b.mp3")));
+        assert_eq!(entries[1].path, None);
+        assert_eq!(entries[1].title, None);
+
+        assert!(read_xspf("", Path::new("/playlists")).1.is_empty());
+    }
+
+    #[test]
+    fn latin1_fallback() {
+        let bytes = b"#EXTINF:10,Beyonc\xe9 - Caf\xe9\ncaf\xe9.mp3\n".to_vec();
+
+        assert_eq!(decode(Path::new("old.m3u"), bytes.clone()), "#EXTINF:10,Beyoncé - Café\ncafé.mp3\n");
+        assert!(decode(Path::new("new.m3u8"), bytes).contains('\u{fffd}'));
+        assert_eq!(decode(Path::new("utf8.m3u"), "café".as_bytes().to_vec()), "café");
+    }
+
+    #[test]
+    fn title_only_entries_need_a_single_song_with_the_same_duration() {
+        let library = vec![
+            song("/music/a/intro.mp3", "Intro", "The xx", Some(127_000)),
+            song("/music/b/intro.mp3", "Intro", "M83", Some(320_000)),
+            song("/music/c/intro.mp3", "Intro", "Alt-J", Some(321_000))
+        ];
+        let songs_by_path = HashMap::new();
+
+        let entry = |duration:Option<u64>| PlaylistEntry { path: None, title: Some("Intro".to_string()), artist: None, duration };
+
+        assert_eq!(find_song(&entry(None), &library, &songs_by_path), None);
+        assert_eq!(find_song(&entry(Some(128_000)), &library, &songs_by_path).map(|song| song.artist), Some("The xx".to_string()));
+        assert_eq!(find_song(&entry(Some(320_000)), &library, &songs_by_path), None);
+        assert_eq!(find_song(&entry(Some(200_000)), &library, &songs_by_path), None);
+
+        let entry = PlaylistEntry { path: None, title: Some("Intro".to_string()), artist: Some("M83".to_string()), duration: None };
+        assert_eq!(find_song(&entry, &library, &songs_by_path).map(|song| song.artist), Some("M83".to_string()));
+    }
+}
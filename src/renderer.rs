@@ -73,6 +73,10 @@ impl Drawable for Song {
             return ImageSource::Uri(url.into());
         }
 
+        if let Some(source) = self.cover.clone().and_then(CacheManager::source_from_id) {
+            return source;
+        }
+
         CacheManager::get_default_cover()
     }
 
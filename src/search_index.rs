@@ -0,0 +1,390 @@
+use std::cmp::Ordering;
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::{database::Database, song::Song};
+
+//words that introduce the featured artists in a title or an artist name
+const FEATURE_MARKERS: [&str; 5] = ["feat.", "feat", "ft.", "ft", "featuring"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchField {
+    Title,
+    Artist,
+    Album,
+    Feature
+}
+
+impl SearchField {
+    fn from_prefix(prefix:&str) -> Option<Self> {
+        match prefix {
+            "title" => Some(SearchField::Title),
+            "artist" => Some(SearchField::Artist),
+            "album" => Some(SearchField::Album),
+            "feat" | "ft" | "feature" => Some(SearchField::Feature),
+            _ => None
+        }
+    }
+
+    fn weight(&self) -> u32 {
+        match self {
+            SearchField::Title => 4,
+            SearchField::Artist => 3,
+            SearchField::Album => 2,
+            SearchField::Feature => 2
+        }
+    }
+}
+
+//lowercase without accents, "Beyoncé" and "beyonce" are the same
+pub fn fold(text:&str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(|c| match c {
+            'ß' => "ss".chars().collect::<Vec<char>>(),
+            'æ' | 'Æ' => "ae".chars().collect(),
+            'œ' | 'Œ' => "oe".chars().collect(),
+            'ø' | 'Ø' => vec!['o'],
+            'ł' | 'Ł' => vec!['l'],
+            'đ' | 'Đ' => vec!['d'],
+            c => c.to_lowercase().collect()
+        })
+        .collect()
+}
+
+fn tokenize(text:&str) -> Vec<String> {
+    fold(text)
+        .split(|c:char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_string())
+        .collect()
+}
+
+//"Song (feat. A & B)" or "Artist ft. A, B" gives A and B
+fn featured_artists(text:&str) -> Vec<String> {
+    let words = fold(text).replace(['(', ')', '[', ']'], " ");
+    let mut words = words.split_whitespace();
+
+    let mut features = vec![];
+    let mut found = false;
+
+    while let Some(word) = words.next() {
+        if FEATURE_MARKERS.contains(&word) {
+            found = true;
+            continue;
+        }
+
+        if found {
+            features.extend(tokenize(word));
+        }
+    }
+
+    features
+}
+
+//optimal string alignment distance, a swap of two letters counts as one typo
+fn distance(a:&[char], b:&[char]) -> usize {
+    let mut previous2 = vec![0; b.len() + 1];
+    let mut previous:Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(previous2[j - 2] + 1);
+            }
+        }
+
+        std::mem::swap(&mut previous2, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+//longer words tolerate more typos, short ones have to be exact
+fn allowed_typos(term:&[char]) -> usize {
+    match term.len() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2
+    }
+}
+
+//3 for the same word, 2 for the beginning of a word, 1 for a word with typos
+fn match_token(term:&[char], token:&[char]) -> u32 {
+    if term == token {
+        return 3;
+    }
+
+    if token.starts_with(term) {
+        return 2;
+    }
+
+    let typos = allowed_typos(term);
+    if typos > 0 {
+        //the word being typed is compared to the beginning of the token too
+        let prefix = &token[..token.len().min(term.len())];
+
+        if token.len().abs_diff(term.len()) <= typos && distance(term, token) <= typos {
+            return 1;
+        }
+
+        if distance(term, prefix) <= typos {
+            return 1;
+        }
+    }
+
+    0
+}
+
+struct Term {
+    field:Option<SearchField>,
+    words:Vec<Vec<char>>
+}
+
+//`artist:daft album:"random access"` gives two terms limited to a field, other words match any field
+fn parse_query(query:&str) -> Vec<Term> {
+    let mut terms = vec![];
+    let mut chars = query.chars().peekable();
+
+    while chars.peek().is_some() {
+        while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            chars.next();
+        }
+
+        let mut word = String::new();
+        let mut quoted = false;
+
+        while let Some(c) = chars.next() {
+            if c == '"' {
+                quoted = !quoted;
+            } else if c.is_whitespace() && !quoted {
+                break;
+            } else {
+                word.push(c);
+            }
+        }
+
+        if word.is_empty() {
+            continue;
+        }
+
+        let (field, value) = match word.split_once(':') {
+            Some((prefix, value)) => match SearchField::from_prefix(&fold(prefix)) {
+                Some(field) => (Some(field), value.to_string()),
+                None => (None, word.clone())
+            },
+            None => (None, word.clone())
+        };
+
+        let words = tokenize(&value).into_iter().map(|word| word.chars().collect()).collect::<Vec<Vec<char>>>();
+        if !words.is_empty() {
+            terms.push(Term { field, words });
+        }
+    }
+
+    terms
+}
+
+struct IndexedSong {
+    song:Song,
+    fields:Vec<(SearchField, Vec<Vec<char>>)>
+}
+
+impl IndexedSong {
+    fn new(song:Song) -> Self {
+        let mut features = featured_artists(&song.title);
+        features.extend(featured_artists(&song.artist));
+
+        let to_chars = |tokens:Vec<String>| tokens.into_iter().map(|token| token.chars().collect()).collect::<Vec<Vec<char>>>();
+
+        let fields = vec![
+            (SearchField::Title, to_chars(tokenize(&song.title))),
+            (SearchField::Artist, to_chars(tokenize(&song.artist))),
+            (SearchField::Album, to_chars(tokenize(&song.album))),
+            (SearchField::Feature, to_chars(features))
+        ];
+
+        IndexedSong { song, fields }
+    }
+
+    //every word of the query has to match somewhere, None when one doesn't
+    fn score(&self, terms:&Vec<Term>) -> Option<u32> {
+        let mut total = 0;
+
+        for term in terms {
+            for word in &term.words {
+                let best = self.fields.iter()
+                    .filter(|(field, _)| term.field.map(|expected| expected == *field).unwrap_or(true))
+                    .flat_map(|(field, tokens)| tokens.iter().map(move |token| match_token(word, token) * field.weight()))
+                    .max()
+                    .unwrap_or(0);
+
+                if best == 0 {
+                    return None;
+                }
+
+                total += best;
+            }
+        }
+
+        Some(total)
+    }
+}
+
+//songs of the library with their words already folded, rebuilt when the database changes
+pub struct SearchIndex {
+    revision:Option<i64>,
+    songs:Vec<IndexedSong>
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        SearchIndex {
+            revision: None,
+            songs: vec![]
+        }
+    }
+
+    fn refresh(&mut self, database:&Database) {
+        let revision = database.songs().revision();
+
+        if revision.is_some() && revision == self.revision {
+            return;
+        }
+
+        self.songs = database.songs().get_songs().into_iter().map(IndexedSong::new).collect();
+        self.revision = revision;
+    }
+
+    //best matches first, the whole library in its order for an empty query
+    pub fn search(&mut self, database:&Database, query:&str) -> Vec<Song> {
+        self.refresh(database);
+
+        rank(&self.songs, &parse_query(query))
+    }
+}
+
+//highest score first, then by title
+fn rank(songs:&Vec<IndexedSong>, terms:&Vec<Term>) -> Vec<Song> {
+    if terms.is_empty() {
+        return songs.iter().map(|indexed| indexed.song.clone()).collect();
+    }
+
+    let mut results = songs.iter()
+        .filter_map(|indexed| indexed.score(terms).map(|score| (score, &indexed.song)))
+        .collect::<Vec<(u32, &Song)>>();
+
+    results.sort_by(|a, b| match b.0.cmp(&a.0) {
+        Ordering::Equal => a.1.title.cmp(&b.1.title),
+        ordering => ordering
+    });
+
+    results.into_iter().map(|(_, song)| song.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(text:&str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn parsed(query:&str) -> Vec<(Option<SearchField>, Vec<String>)> {
+        parse_query(query).into_iter()
+            .map(|term| (term.field, term.words.into_iter().map(|word| word.into_iter().collect()).collect()))
+            .collect()
+    }
+
+    fn words(words:&[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    fn titles(query:&str, songs:&[(&str, &str)]) -> Vec<String> {
+        let songs = songs.iter()
+            .map(|(title, artist)| IndexedSong::new(Song::new(Default::default(), title.to_string(), artist.to_string(), "Unknown".to_string())))
+            .collect::<Vec<IndexedSong>>();
+
+        rank(&songs, &parse_query(query)).into_iter().map(|song| song.title).collect()
+    }
+
+    #[test]
+    fn fold_removes_accents_and_case() {
+        assert_eq!(fold("Beyoncé"), "beyonce");
+        assert_eq!(fold("SIGUR RÓS"), "sigur ros");
+        assert_eq!(fold("Straße"), "strasse");
+        assert_eq!(fold("Ærøskøbing"), "aeroskobing");
+        assert_eq!(fold("Œuvre Łódź Đorđe"), "oeuvre lodz dorde");
+    }
+
+    #[test]
+    fn distance_counts_a_transposition_as_one_typo() {
+        assert_eq!(distance(&chars("daft"), &chars("daft")), 0);
+        assert_eq!(distance(&chars("daft"), &chars("dfat")), 1);
+        assert_eq!(distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(distance(&chars(""), &chars("abc")), 3);
+        //optimal string alignment, a transposed pair isn't edited again
+        assert_eq!(distance(&chars("ca"), &chars("abc")), 3);
+    }
+
+    #[test]
+    fn match_token_scores() {
+        assert_eq!(match_token(&chars("daft"), &chars("daft")), 3);
+        assert_eq!(match_token(&chars("daf"), &chars("daft")), 2);
+        assert_eq!(match_token(&chars("beyonse"), &chars("beyonce")), 1);
+        assert_eq!(match_token(&chars("dfat"), &chars("daft")), 1);
+        assert_eq!(match_token(&chars("dfa"), &chars("daft")), 0);
+    }
+
+    #[test]
+    fn parse_query_fields_and_quotes() {
+        assert_eq!(parsed("artist:\"Daft Punk\" one more"), vec![
+            (Some(SearchField::Artist), words(&["daft", "punk"])),
+            (None, words(&["one"])),
+            (None, words(&["more"]))
+        ]);
+
+        assert_eq!(parsed("\"random access\"  Memories"), vec![
+            (None, words(&["random", "access"])),
+            (None, words(&["memories"]))
+        ]);
+
+        assert_eq!(parsed("FT:drake album:\"random access"), vec![
+            (Some(SearchField::Feature), words(&["drake"])),
+            (Some(SearchField::Album), words(&["random", "access"]))
+        ]);
+
+        assert_eq!(parsed("genre:house"), vec![(None, words(&["genre", "house"]))]);
+        assert!(parsed("   \"\" ").is_empty());
+    }
+
+    #[test]
+    fn ranking_order() {
+        let songs = [
+            ("Dart", "Nobody"),
+            ("Daftendirekt", "Nobody"),
+            ("Around the World", "Daft Punk"),
+            ("Hello", "Adele"),
+            ("Daft", "Nobody"),
+            ("Aerodynamic", "Daft Punk")
+        ];
+
+        assert_eq!(titles("daft", &songs), vec!["Daft", "Aerodynamic", "Around the World", "Daftendirekt", "Dart"]);
+        assert_eq!(titles("artist:daft world", &songs), vec!["Around the World"]);
+        assert_eq!(titles("", &songs).len(), songs.len());
+    }
+
+    #[test]
+    fn featured_artists_are_searchable() {
+        let songs = [("Work (feat. Drake)", "Rihanna"), ("Hotline Bling", "Drake")];
+
+        assert_eq!(titles("feat:drake", &songs), vec!["Work (feat. Drake)"]);
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use crate::{gui::helper::simplify, history::SongStats, song::Song};
+
+//only for songs whose codec wasn't read, an m4a can be aac or alac so it isn't in the list
+const LOSSLESS_EXTENSIONS: [&str; 4] = ["flac", "wav", "aiff", "ape"];
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TextField {
+    Title,
+    Artist,
+    Album
+}
+
+impl ToString for TextField {
+    fn to_string(&self) -> String {
+        match self {
+            TextField::Title => "Title".to_string(),
+            TextField::Artist => "Artist".to_string(),
+            TextField::Album => "Album".to_string()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum TextMatch {
+    Contains,
+    Is,
+    IsNot
+}
+
+impl ToString for TextMatch {
+    fn to_string(&self) -> String {
+        match self {
+            TextMatch::Contains => "contains".to_string(),
+            TextMatch::Is => "is".to_string(),
+            TextMatch::IsNot => "is not".to_string()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum Rule {
+    Text { field:TextField, matching:TextMatch, value:String },
+    AddedBetween { from:Option<u64>, to:Option<u64> }, //seconds since the unix epoch, to is excluded
+    PlayCount { min:Option<u64>, max:Option<u64> },
+    Lossless(bool),
+    Duration { min:Option<u64>, max:Option<u64> } //ms
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Text { .. } => "Text",
+            Rule::AddedBetween { .. } => "Added between",
+            Rule::PlayCount { .. } => "Play count",
+            Rule::Lossless(_) => "Lossless",
+            Rule::Duration { .. } => "Duration"
+        }
+    }
+
+    //one rule of each kind, used by the editor
+    pub fn defaults() -> Vec<Rule> {
+        vec![
+            Rule::Text { field: TextField::Artist, matching: TextMatch::Contains, value: String::new() },
+            Rule::AddedBetween { from: None, to: None },
+            Rule::PlayCount { min: None, max: Some(0) },
+            Rule::Lossless(true),
+            Rule::Duration { min: None, max: None }
+        ]
+    }
+
+    pub fn matches(&self, song:&Song, stats:&SongStats) -> bool {
+        let in_range = |value:u64, min:&Option<u64>, max:&Option<u64>| {
+            min.map(|min| value >= min).unwrap_or(true) && max.map(|max| value <= max).unwrap_or(true)
+        };
+
+        match self {
+            Rule::Text { field, matching, value } => {
+                let text = match field {
+                    TextField::Title => &song.title,
+                    TextField::Artist => &song.artist,
+                    TextField::Album => &song.album
+                };
+
+                let text = simplify(text);
+                let value = simplify(value);
+
+                match matching {
+                    TextMatch::Contains => text.contains(value.trim()),
+                    TextMatch::Is => text.trim() == value.trim(),
+                    TextMatch::IsNot => text.trim() != value.trim()
+                }
+            },
+            //songs added before the date was recorded never match
+            Rule::AddedBetween { from, to } => song.added_at.map(|added_at| {
+                from.map(|from| added_at >= from).unwrap_or(true) && to.map(|to| added_at < to).unwrap_or(true)
+            }).unwrap_or(false),
+            Rule::PlayCount { min, max } => in_range(stats.play_count, min, max),
+            Rule::Lossless(lossless) => is_lossless(song) == *lossless,
+            Rule::Duration { min, max } => song.duration.map(|duration| in_range(duration, min, max)).unwrap_or(false)
+        }
+    }
+}
+
+pub fn is_lossless(song:&Song) -> bool {
+    song.lossless.unwrap_or_else(|| {
+        song.path.extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| LOSSLESS_EXTENSIONS.contains(&extension.to_lowercase().as_str()))
+            .unwrap_or(false)
+    })
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SmartRules {
+    pub match_all:bool,
+    pub rules:Vec<Rule>,
+    #[serde(default)]
+    pub limit:Option<usize>
+}
+
+impl Default for SmartRules {
+    fn default() -> Self {
+        SmartRules {
+            match_all: true,
+            rules: Vec::new(),
+            limit: None
+        }
+    }
+}
+
+impl SmartRules {
+    pub fn matches(&self, song:&Song, stats:&SongStats) -> bool {
+        if self.rules.is_empty() {
+            return true;
+        }
+
+        if self.match_all {
+            self.rules.iter().all(|rule| rule.matches(song, stats))
+        } else {
+            self.rules.iter().any(|rule| rule.matches(song, stats))
+        }
+    }
+
+    //newest songs first
+    pub fn evaluate(&self, songs:Vec<Song>, stats:&HashMap<String, SongStats>) -> Vec<Song> {
+        let no_stats = SongStats::default();
+
+        let mut songs = songs.into_iter()
+            .filter(|song| self.matches(song, stats.get(&song.id).unwrap_or(&no_stats)))
+            .collect::<Vec<Song>>();
+
+        songs.sort_by(|a, b| b.added_at.cmp(&a.added_at).then_with(|| a.title.cmp(&b.title)));
+
+        if let Some(limit) = self.limit {
+            songs.truncate(limit);
+        }
+
+        songs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn song(path:&str, title:&str, artist:&str, album:&str) -> Song {
+        Song::new(PathBuf::from(path), title.to_string(), artist.to_string(), album.to_string())
+    }
+
+    fn stats(play_count:u64) -> SongStats {
+        SongStats { play_count, last_played: None }
+    }
+
+    fn text(field:TextField, matching:TextMatch, value:&str) -> Rule {
+        Rule::Text { field, matching, value: value.to_string() }
+    }
+
+    #[test]
+    fn text_rules() {
+        let song = song("/music/a.flac", "Déjà Vu", "Beyoncé", "B'Day");
+        let none = stats(0);
+
+        assert!(text(TextField::Title, TextMatch::Contains, "deja").matches(&song, &none));
+        assert!(!text(TextField::Title, TextMatch::Contains, "vous").matches(&song, &none));
+        assert!(text(TextField::Artist, TextMatch::Is, " BEYONCE ").matches(&song, &none));
+        assert!(!text(TextField::Artist, TextMatch::Is, "beyon").matches(&song, &none));
+        assert!(text(TextField::Album, TextMatch::IsNot, "Lemonade").matches(&song, &none));
+        assert!(!text(TextField::Album, TextMatch::IsNot, "b'day").matches(&song, &none));
+    }
+
+    #[test]
+    fn added_between_excludes_the_end() {
+        let mut song = song("/music/a.flac", "A", "B", "C");
+        let rule = Rule::AddedBetween { from: Some(100), to: Some(200) };
+
+        assert!(!rule.matches(&song, &stats(0)));
+
+        for (added_at, expected) in [(99, false), (100, true), (199, true), (200, false)] {
+            song.added_at = Some(added_at);
+            assert_eq!(rule.matches(&song, &stats(0)), expected, "added at {}", added_at);
+        }
+
+        song.added_at = Some(0);
+        assert!(Rule::AddedBetween { from: None, to: None }.matches(&song, &stats(0)));
+    }
+
+    #[test]
+    fn play_count_bounds_are_inclusive() {
+        let song = song("/music/a.flac", "A", "B", "C");
+        let rule = Rule::PlayCount { min: Some(2), max: Some(5) };
+
+        assert!(!rule.matches(&song, &stats(1)));
+        assert!(rule.matches(&song, &stats(2)));
+        assert!(rule.matches(&song, &stats(5)));
+        assert!(!rule.matches(&song, &stats(6)));
+        assert!(Rule::PlayCount { min: None, max: Some(0) }.matches(&song, &stats(0)));
+    }
+
+    #[test]
+    fn lossless_uses_the_codec_before_the_extension() {
+        let mut alac = song("/music/a.m4a", "A", "B", "C");
+        let mut flac = song("/music/b.flac", "A", "B", "C");
+        let mut aac = song("/music/c.m4a", "A", "B", "C");
+
+        assert!(!is_lossless(&alac));
+        assert!(is_lossless(&flac));
+
+        alac.lossless = Some(true);
+        flac.lossless = Some(false);
+        aac.lossless = Some(false);
+
+        assert!(Rule::Lossless(true).matches(&alac, &stats(0)));
+        assert!(Rule::Lossless(false).matches(&flac, &stats(0)));
+        assert!(Rule::Lossless(false).matches(&aac, &stats(0)));
+    }
+
+    #[test]
+    fn duration_rules() {
+        let mut song = song("/music/a.flac", "A", "B", "C");
+        let rule = Rule::Duration { min: Some(60_000), max: Some(300_000) };
+
+        assert!(!rule.matches(&song, &stats(0)));
+
+        song.duration = Some(60_000);
+        assert!(rule.matches(&song, &stats(0)));
+        song.duration = Some(300_001);
+        assert!(!rule.matches(&song, &stats(0)));
+    }
+
+    #[test]
+    fn match_all_or_any() {
+        let song = song("/music/a.flac", "A", "Daft Punk", "C");
+        let rules = vec![text(TextField::Artist, TextMatch::Is, "daft punk"), Rule::PlayCount { min: Some(1), max: None }];
+
+        assert!(!SmartRules { match_all: true, rules: rules.clone(), limit: None }.matches(&song, &stats(0)));
+        assert!(SmartRules { match_all: false, rules, limit: None }.matches(&song, &stats(0)));
+        assert!(SmartRules::default().matches(&song, &stats(0)));
+    }
+
+    #[test]
+    fn evaluate_sorts_newest_first_and_limits() {
+        let mut songs = vec![];
+        for (title, added_at) in [("B", 10), ("A", 30), ("C", 20), ("D", 30)] {
+            let mut song = song("/music/a.flac", title, "X", "Y");
+            song.added_at = Some(added_at);
+            songs.push(song);
+        }
+
+        let rules = SmartRules { match_all: true, rules: vec![text(TextField::Title, TextMatch::IsNot, "c")], limit: Some(2) };
+        let titles = rules.evaluate(songs, &HashMap::new()).into_iter().map(|song| song.title).collect::<Vec<String>>();
+
+        assert_eq!(titles, vec!["A", "D"]);
+    }
+}
@@ -1,32 +1,37 @@
-use std::{collections::hash_map::DefaultHasher, hash::{self, Hash, Hasher}, path::PathBuf, sync::Arc};
+use std::{path::PathBuf, sync::Arc};
 use egui::Response;
 use tidal_rs::model::{Album, Track};
 
-use crate::{app::{self, App, AppImpl}, gui::model::{Pages, UserLocation}, playlist::Playlist, renderer::Drawable};
+use crate::{app::{self, App, AppImpl}, gui::{components::bulk_actions::add_to_playlist_menu, model::{Pages, UserLocation}}, playlist::Playlist, renderer::Drawable, undo::{self, Command}};
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, Hash, PartialEq)]
 pub struct Song {
+    #[serde(default)]
+    pub id:String, //"tidal:<track id>" for downloaded songs, "local:<uuid>" otherwise
     pub path:PathBuf,
     pub title:String,
     pub artist:String,
     pub album:String,
+    #[serde(default)]
+    pub track_number:Option<u32>,
+    #[serde(default)]
+    pub duration:Option<u64>, //in milliseconds
+    #[serde(default)]
+    pub cover:Option<String>, //id of the cover in the cache, used by songs that don't come from tidal
+    #[serde(default)]
+    pub added_at:Option<u64>, //seconds since the unix epoch, set when the song is added to the library
+    #[serde(default)]
+    pub lossless:Option<bool>, //read from the codec of the file, None for songs added before it was
     pub tidal_track:Option<Track>
 }
 
-pub fn resolve_hashed_songs(app:Arc<app::AppImpl>, album:&Album) -> Vec<u64> {
-    let raw = app.database().raw();
-    let tracks = raw.data.lock().unwrap().albums.iter().find(|x| x.album.id == album.id).and_then(|x| Some(x.tracks.clone()));
-
-    tracks.unwrap_or(vec![]) 
+pub fn resolve_album_song_ids(app:Arc<app::AppImpl>, album:&Album) -> Vec<String> {
+    app.database().albums().get_album_song_ids(album)
 }
 
 impl Song {
     pub fn resolve(app:Arc<AppImpl>, track:&Track) -> Option<Song> {
-        let raw = app.database().raw();
-        let data = raw.data.lock().unwrap();
-        let result = data.tracks.0.iter().find(|x| x.1.tidal_track.as_ref() == Some(track));
-
-        result.and_then(|x| Some(x.1.clone()))
+        app.database().songs().get_song_by_track(track)
     }
 
     
@@ -43,18 +48,14 @@ impl Song {
                 let mut queue = app.player.queue();
 
                 //verifier si la playlist actuelle a besoin d'etre changée
-                //current hashed queue
-                let hashed_queue = queue.get_playlist().iter().map(|song| {
-                    let mut hasher = DefaultHasher::new();
-                    song.hash(&mut hasher);
-                    hasher.finish()
-                }).collect::<Vec<u64>>();
-
-                let hashed_playlist = {
-                    playlist.hash_songs(app.clone())
+                //ids of the current queue
+                let queue_ids = queue.get_playlist().iter().map(|song| song.id.clone()).collect::<Vec<String>>();
+
+                let playlist_ids = {
+                    playlist.song_ids(app.clone())
                 };
 
-                if hashed_queue != hashed_playlist {
+                if queue_ids != playlist_ids {
                     let songs = {
                         app.database().playlists().unhash_playlist_songs(&playlist)
                     };
@@ -62,11 +63,7 @@ impl Song {
                     if let Some(resolved_playlist) = songs {
                         queue.set_playlist(&resolved_playlist.songs);
 
-                        let mut hasher = DefaultHasher::new();
-                        self.hash(&mut hasher);
-                        let hash = hasher.finish();
-    
-                        if let Some(index) = hashed_playlist.iter().position(|x| x == &hash) {
+                        if let Some(index) = resolved_playlist.songs.iter().position(|x| x.id == self.id) {
                             queue.current_index = Some(index);
                         }
                     }
@@ -77,52 +74,92 @@ impl Song {
                 let _ = app.player.set_media(&self);
                 let mut queue = app.player.queue();
 
-                let hashed_queue = queue.get_playlist().iter().map(|song| {
-                    let mut hasher = DefaultHasher::new();
-                    song.hash(&mut hasher);
-                    hasher.finish()
-                }).collect::<Vec<u64>>();
+                let queue_ids = queue.get_playlist().iter().map(|song| song.id.clone()).collect::<Vec<String>>();
 
-                let hashed_album = {
-                    resolve_hashed_songs(app.clone(), &album)
+                let album_ids = {
+                    resolve_album_song_ids(app.clone(), &album)
                 };
 
-                if hashed_queue != hashed_album {
+                if queue_ids != album_ids {
                     let songs = {
-                        app.database().albums().get_album_tracks(app.clone(), &album)
+                        app.database().albums().get_album_tracks(&album)
                     };
 
 
                         queue.set_playlist(&songs);
 
-                        let mut hasher = DefaultHasher::new();
-                        self.hash(&mut hasher);
-                        let hash = hasher.finish();
-    
-                        if let Some(index) = hashed_album.iter().position(|x| x == &hash) {
+                        if let Some(index) = songs.iter().position(|x| x.id == self.id) {
                             queue.current_index = Some(index);
                         }
                     
                 }
             },
             UserLocation::Artist(artist) => {
+                let _ = app.player.set_media(&self);
+                let mut queue = app.player.queue();
+
+                let queue_ids = queue.get_playlist().iter().map(|song| song.id.clone()).collect::<Vec<String>>();
+
+                let songs = {
+                    app.database().artists().get_artist_songs(&artist)
+                };
+
+                if queue_ids != songs.iter().map(|song| song.id.clone()).collect::<Vec<String>>() {
+                    queue.set_playlist(&songs);
+
+                    if let Some(index) = songs.iter().position(|x| x.id == self.id) {
+                        queue.current_index = Some(index);
+                    }
+                }
             }
         }
     }
 
-    pub fn context_menu(&self, response:Response, ui:&mut egui::Ui, application:&app::App,) {
+    pub fn context_menu(&self, response:Response, ui:&mut egui::Ui, application:&mut app::App,) {
         response.context_menu(|ui| {
-            if ui.button("Add to playlist").clicked() { //todo: add to playlist
+            //right clicking one of the selected songs acts on the whole selection
+            let selection = &application.gui_settings.selection;
+            if selection.len() > 1 && selection.songs().iter().any(|song| song.id == self.id) {
+                let songs = selection.songs().clone();
+                application.bulk_actions_menu(ui, songs);
+                return;
             }
+
+            add_to_playlist_menu(ui, &application.app, &vec![self.clone()]);
     
             if application.gui_settings.page == Pages::Playlist {
                 if let UserLocation::Playlist(playlist) = &application.gui_settings.location {
     
     
                     if ui.button("Remove from playlist").clicked() {
-                        application.app.database().playlists().remove_from_playlist(playlist, &self);
+                        if let Err(e) = undo::execute(&application.app, Command::remove_from_playlist(playlist, vec![self.clone()])) {
+                            println!("Failed to remove from playlist : {}", e);
+                        }
                         ui.close_menu();
                     }
+
+                    //smart playlists are ordered by their rules
+                    if !playlist.smart {
+                        let song_ids = {
+                            playlist.song_ids(application.app.clone())
+                        };
+
+                        if let Some(position) = song_ids.iter().position(|id| *id == self.id) {
+                            if position > 0 && ui.button("Move up").clicked() {
+                                if let Err(e) = undo::execute(&application.app, Command::move_song(playlist, position, position - 1)) {
+                                    println!("Failed to move song : {}", e);
+                                }
+                                ui.close_menu();
+                            }
+
+                            if position + 1 < song_ids.len() && ui.button("Move down").clicked() {
+                                if let Err(e) = undo::execute(&application.app, Command::move_song(playlist, position, position + 1)) {
+                                    println!("Failed to move song : {}", e);
+                                }
+                                ui.close_menu();
+                            }
+                        }
+                    }
                 }
             }
     
@@ -130,6 +167,20 @@ impl Song {
                 let mut queue = application.app.player.queue();
                 queue.add_to_queue(&self);
             }
+
+            if ui.button("Remove from library").clicked() {
+                if let Err(e) = undo::execute(&application.app, Command::remove_songs(vec![self.clone()])) {
+                    println!("Failed to remove song : {}", e);
+                }
+                ui.close_menu();
+            }
+
+            if ui.button("Delete file").on_hover_text("The file is moved to the trash and can be restored from the settings").clicked() {
+                if let Err(e) = undo::execute(&application.app, Command::trash_songs(vec![self.clone()])) {
+                    println!("Failed to trash song : {}", e);
+                }
+                ui.close_menu();
+            }
     
             let mixes = self.tidal_track.as_ref().and_then(|track| track.mixes.as_ref().and_then(|mixes| Some(mixes.clone())));
             
@@ -150,10 +201,14 @@ impl Song {
                                 id: mix.to_string(),
                                 name: format!("Radio - {}", track_name),
                                 image: None,
-                                songs: vec![]
+                                description: None,
+                                songs: vec![],
+                                rules: None
                             };
     
-                            app.database().playlists().add_playlist(&playlist);
+                            if let Err(e) = app.database().playlists().add_playlist(&playlist) {
+                                println!("Failed to create playlist : {}", e);
+                            }
     
                             for track in tracks {
                                 app.download_manager.enqueue_single(app.clone(), quality, track.clone(), Some(&playlist)).await;        
@@ -173,22 +228,41 @@ impl Song {
 }
 
 impl Song {
+    pub fn generate_id(tidal_track:Option<&Track>) -> String {
+        match tidal_track {
+            Some(track) => format!("tidal:{}", track.id),
+            None => format!("local:{}", uuid::Uuid::new_v4())
+        }
+    }
+
     pub fn new(path:PathBuf, title:String, artist:String, album:String) -> Self {
         Song {
+            id:Song::generate_id(None),
             path,
             title,
             artist,
             album,
+            track_number:None,
+            duration:None,
+            cover:None,
+            added_at:None,
+            lossless:None,
             tidal_track:None
         }
     }
 
     pub fn new_with_track(path:PathBuf, tidal_track:Track) -> Self {
         Song {
+            id:Song::generate_id(Some(&tidal_track)),
             path,
             title:tidal_track.title.clone(),
             artist:tidal_track.get_artist().name.clone(),
             album:tidal_track.album.clone().map(|x| x.title).unwrap_or("Unknown".to_string()),
+            track_number:None,
+            duration:None,
+            cover:None,
+            added_at:None,
+            lossless:None,
             tidal_track:Some(tidal_track)
         }
     }
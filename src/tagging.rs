@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use lofty::{Accessor, AudioFile, FileProperties, ItemKey, ItemValue, Picture, PictureType, Probe, Tag, TagExt, TagItem, TagType, TaggedFileExt};
+use tidal_rs::model::Track;
+
+//biggest size tidal serves for album covers
+const COVER_SIZE: &str = "1280x1280";
+
+//mp4 only keeps the custom fields written as freeform atoms
+pub fn tidal_id_key(tag_type:TagType) -> ItemKey {
+    match tag_type {
+        TagType::Mp4Ilst => ItemKey::Unknown("----:com.apple.iTunes:TIDAL_ID".to_string()),
+        _ => ItemKey::Unknown("TIDAL_ID".to_string())
+    }
+}
+
+pub fn read_tidal_id(tag:&Tag) -> Option<u64> {
+    tag.get_string(&tidal_id_key(tag.tag_type())).and_then(|id| id.trim().parse().ok())
+}
+
+pub async fn download_cover(track:&Track) -> Option<Vec<u8>> {
+    let cover = track.album.as_ref().map(|album| album.cover.clone())?;
+    let url = format!("https://resources.tidal.com/images/{}/{}.jpg", cover.replace("-", "/"), COVER_SIZE);
+
+    let response = reqwest::get(url).await.ok()?.error_for_status().ok()?;
+    response.bytes().await.ok().map(|bytes| bytes.to_vec())
+}
+
+//replaces what tidal ships in the file with the metadata of the track, flac files get vorbis comments and m4a files mp4 atoms
+//the type is guessed from the content, the file still has its .part extension
+//alac, flac and wav have a bit depth, aac, mp3 and the other lossy codecs don't
+pub fn is_lossless(properties:&FileProperties) -> bool {
+    properties.bit_depth().is_some()
+}
+
+pub fn read_lossless(path:&Path) -> Result<bool, lofty::LoftyError> {
+    let tagged_file = Probe::open(path)?.guess_file_type()?.read()?;
+    Ok(is_lossless(tagged_file.properties()))
+}
+
+pub fn write_tags(path:&Path, track:&Track, cover:Option<Vec<u8>>) -> Result<(), lofty::LoftyError> {
+    let mut tagged_file = Probe::open(path)?.guess_file_type()?.read()?;
+    let tag_type = tagged_file.primary_tag_type();
+
+    if tagged_file.primary_tag().is_none() {
+        tagged_file.insert_tag(Tag::new(tag_type));
+    }
+
+    let Some(tag) = tagged_file.primary_tag_mut() else {
+        return Ok(());
+    };
+
+    let main_artist = track.get_artist().name;
+
+    tag.set_title(track.title.clone());
+    tag.set_track(track.track_number as u32);
+    tag.set_disk(track.volume_number as u32);
+
+    //vorbis comments can hold one field per artist, the other formats get them joined
+    tag.remove_key(&ItemKey::TrackArtist);
+    if tag_type == TagType::VorbisComments {
+        for artist in &track.artists {
+            tag.push(TagItem::new(ItemKey::TrackArtist, ItemValue::Text(artist.name.clone())));
+        }
+    } else {
+        let artists = track.artists.iter().map(|artist| artist.name.clone()).collect::<Vec<String>>();
+        tag.insert_text(ItemKey::TrackArtist, artists.join(", "));
+    }
+
+    tag.insert_text(ItemKey::AlbumArtist, main_artist);
+    tag.insert_text(ItemKey::Isrc, track.isrc.clone());
+    tag.insert_text(tidal_id_key(tag_type), track.id.to_string());
+
+    if let Some(album) = &track.album {
+        tag.set_album(album.title.clone());
+
+        if let Some(release_date) = &album.release_date {
+            tag.insert_text(ItemKey::RecordingDate, release_date.clone());
+        }
+    }
+
+    if let Some(copyright) = &track.copyright {
+        tag.insert_text(ItemKey::CopyrightMessage, copyright.clone());
+    }
+
+    tag.insert_text(ItemKey::ReplayGainTrackGain, format!("{:.2} dB", track.replay_gain));
+    tag.insert_text(ItemKey::ReplayGainTrackPeak, format!("{:.6}", track.peak));
+
+    if let Some(cover) = cover {
+        let mut picture = Picture::from_reader(&mut cover.as_slice())?;
+        picture.set_pic_type(PictureType::CoverFront);
+
+        tag.remove_picture_type(PictureType::CoverFront);
+        tag.push_picture(picture);
+    }
+
+    tag.save_to_path(path)
+}
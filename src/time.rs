@@ -1,3 +1,5 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 
 
 pub fn ms_to_min_sec(ms: u64) -> String {
@@ -5,4 +7,108 @@ pub fn ms_to_min_sec(ms: u64) -> String {
     let minutes = seconds / 60;
     let remaining_seconds = seconds % 60;
     format!("{:02}:{:02}", minutes, remaining_seconds)
-}
\ No newline at end of file
+}
+pub fn secs_to_age(secs: u64) -> String {
+    let minutes = secs / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+
+    if days > 0 {
+        format!("{} days ago", days)
+    } else if hours > 0 {
+        format!("{} hours ago", hours)
+    } else if minutes > 0 {
+        format!("{} minutes ago", minutes)
+    } else {
+        "just now".to_string()
+    }
+}
+
+//seconds since the unix epoch
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+//days since the unix epoch of a date of the proleptic gregorian calendar (http://howardhinnant.github.io/date_algorithms.html)
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = (if year >= 0 { year } else { year - 399 }) / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = (if days >= 0 { days } else { days - 146096 }) / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = (if month_index < 10 { month_index + 3 } else { month_index - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+//first second of the year, dates before the unix epoch are clamped to it
+pub fn year_start(year: i64) -> u64 {
+    days_from_civil(year, 1, 1).max(0) as u64 * 86400
+}
+
+pub fn year_of(secs: u64) -> i64 {
+    civil_from_days((secs / 86400) as i64).0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_dates_round_trip() {
+        let dates = [
+            (1970, 1, 1, 0),
+            (1969, 12, 31, -1),
+            (2000, 2, 28, 11015),
+            (2000, 2, 29, 11016),
+            (2000, 3, 1, 11017),
+            (1900, 3, 1, -25508),
+            (2023, 12, 31, 19722),
+            (2024, 1, 1, 19723),
+            (2024, 2, 29, 19782)
+        ];
+
+        for (year, month, day, days) in dates {
+            assert_eq!(days_from_civil(year, month, day), days, "{}-{}-{}", year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+
+        //1900 isn't a leap year, the day after february 28 is march 1
+        assert_eq!(days_from_civil(1900, 3, 1) - days_from_civil(1900, 2, 28), 1);
+
+        for days in -800_000..800_000 {
+            let (year, month, day) = civil_from_days(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn year_bounds() {
+        assert_eq!(year_start(1970), 0);
+        assert_eq!(year_start(1960), 0);
+        assert_eq!(year_start(2024), 1_704_067_200);
+        assert_eq!(year_start(2025), 1_735_689_600);
+
+        assert_eq!(year_of(0), 1970);
+        assert_eq!(year_of(year_start(2024)), 2024);
+        assert_eq!(year_of(year_start(2024) - 1), 2023);
+        assert_eq!(year_of(year_start(2025) - 1), 2024);
+        //december 31 of a leap year is still in it
+        assert_eq!(year_of(year_start(2024) + 365 * 86400), 2024);
+        assert_eq!(year_of(year_start(2024) + 366 * 86400), 2025);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{path::{Path, PathBuf}, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+
+use crate::{app::AppImpl, song::Song};
+
+#[derive(Clone)]
+pub struct TrashEntry {
+    pub song:Song,
+    pub trash_path:PathBuf,
+    pub deleted_at:u64
+}
+
+fn to_io_error(e:rusqlite::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e)
+}
+
+pub fn get_trash_folder() -> PathBuf {
+    let path_buf = if let Ok(program_data) = std::env::var("PROGRAMDATA") {
+        PathBuf::from(program_data)
+            .join("Localfy")
+            .join("trash")
+    } else {
+        PathBuf::from("trash")
+    };
+
+    if !path_buf.exists() {
+        let _ = std::fs::create_dir_all(&path_buf);
+    }
+
+    path_buf
+}
+
+//rename doesn't work across drives, copy the file instead
+fn move_file(from:&Path, to:&Path) -> std::io::Result<()> {
+    if let Some(parent) = to.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    if std::fs::rename(from, to).is_err() {
+        std::fs::copy(from, to)?;
+        std::fs::remove_file(from)?;
+    }
+
+    Ok(())
+}
+
+pub fn move_to_trash(app:&Arc<AppImpl>, song:&Song) -> std::io::Result<TrashEntry> {
+    let extension = song.path.extension().map(|x| x.to_string_lossy().to_string()).unwrap_or_default();
+    let trash_path = get_trash_folder().join(format!("{}.{}", song.id.replace(":", "_"), extension));
+    let deleted_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+    //the song leaves the database first so the library watcher doesn't react to the file disappearing
+    app.database().trash().add(song, &trash_path, deleted_at).map_err(to_io_error)?;
+
+    let entry = TrashEntry { song: song.clone(), trash_path, deleted_at };
+
+    if let Err(e) = move_file(&song.path, &entry.trash_path) {
+        let _ = app.database().trash().restore(&entry);
+        return Err(e);
+    }
+
+    Ok(entry)
+}
+
+pub fn restore(app:&Arc<AppImpl>, entry:&TrashEntry) -> std::io::Result<()> {
+    if entry.song.path.exists() {
+        return Err(std::io::Error::new(std::io::ErrorKind::AlreadyExists, "A file already exists at the original location"));
+    }
+
+    //the song is back in the database before the file so the library watcher doesn't import it a second time
+    app.database().trash().restore(entry).map_err(to_io_error)?;
+
+    if let Err(e) = move_file(&entry.trash_path, &entry.song.path) {
+        let _ = app.database().trash().add(&entry.song, &entry.trash_path, entry.deleted_at);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+pub fn delete_permanently(app:&Arc<AppImpl>, entry:&TrashEntry) -> std::io::Result<()> {
+    if let Err(e) = std::fs::remove_file(&entry.trash_path) {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            return Err(e);
+        }
+    }
+
+    app.database().trash().remove(entry).map_err(to_io_error)
+}
+
+pub fn empty(app:&Arc<AppImpl>) -> std::io::Result<()> {
+    let entries = {
+        app.database().trash().get_entries()
+    };
+
+    for entry in entries {
+        delete_permanently(app, &entry)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,282 @@
+use std::{sync::Arc, time::{Duration, Instant}};
+
+use tidal_rs::model::Album;
+
+use crate::{app::AppImpl, database::SongMemberships, playlist::PlaylistDescriptor, song::Song, trash::{self, TrashEntry}};
+
+const MAX_COMMANDS: usize = 100;
+const TOAST_DURATION: Duration = Duration::from_secs(6);
+
+fn to_io_error(e:rusqlite::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e)
+}
+
+//a change to the library that can be reverted, what is needed to revert it is filled when it is applied
+pub enum Command {
+    AddToPlaylist { playlist:PlaylistDescriptor, songs:Vec<Song>, added:Vec<Song> },
+    RemoveFromPlaylist { playlist:PlaylistDescriptor, songs:Vec<Song>, positions:Vec<(Song, i64)> },
+    MoveSong { playlist:PlaylistDescriptor, from:usize, to:usize },
+    EditPlaylist { playlist:PlaylistDescriptor, name:String, description:Option<String> },
+    RemoveSongs { songs:Vec<Song>, memberships:Vec<(Song, SongMemberships)> },
+    TrashSongs { songs:Vec<Song>, entries:Vec<TrashEntry>, failures:Vec<String> },
+    RemoveAlbum { album:Album, tracks:Vec<Song> }
+}
+
+impl Command {
+    pub fn add_to_playlist(playlist:&PlaylistDescriptor, songs:Vec<Song>) -> Self {
+        Command::AddToPlaylist { playlist: playlist.clone(), songs, added: vec![] }
+    }
+
+    pub fn remove_from_playlist(playlist:&PlaylistDescriptor, songs:Vec<Song>) -> Self {
+        Command::RemoveFromPlaylist { playlist: playlist.clone(), songs, positions: vec![] }
+    }
+
+    pub fn move_song(playlist:&PlaylistDescriptor, from:usize, to:usize) -> Self {
+        Command::MoveSong { playlist: playlist.clone(), from, to }
+    }
+
+    pub fn edit_playlist(playlist:&PlaylistDescriptor, name:String, description:Option<String>) -> Self {
+        Command::EditPlaylist { playlist: playlist.clone(), name, description }
+    }
+
+    pub fn remove_songs(songs:Vec<Song>) -> Self {
+        Command::RemoveSongs { songs, memberships: vec![] }
+    }
+
+    pub fn trash_songs(songs:Vec<Song>) -> Self {
+        Command::TrashSongs { songs, entries: vec![], failures: vec![] }
+    }
+
+    pub fn remove_album(album:&Album) -> Self {
+        Command::RemoveAlbum { album: album.clone(), tracks: vec![] }
+    }
+
+    //false when the command changed nothing, it isn't worth undoing
+    fn apply(&mut self, app:&Arc<AppImpl>) -> std::io::Result<bool> {
+        match self {
+            Command::AddToPlaylist { playlist, songs, added } => {
+                let playlists = app.database().playlists();
+
+                //songs already in the playlist must stay there when this is undone
+                let existing = playlists.get_playlist_song_ids(playlist);
+                *added = songs.iter().filter(|song| !existing.contains(&song.id)).cloned().collect();
+
+                playlists.push_to_playlist(playlist, songs).map_err(to_io_error)?;
+
+                Ok(true)
+            },
+            Command::RemoveFromPlaylist { playlist, songs, positions } => {
+                let playlists = app.database().playlists();
+
+                *positions = songs.iter()
+                    .filter_map(|song| playlists.get_song_position(playlist, song).map(|position| (song.clone(), position)))
+                    .collect();
+
+                //put back from the first index, each song then finds the ones before it in place
+                positions.sort_by_key(|(_, position)| *position);
+
+                for song in songs.iter() {
+                    playlists.remove_from_playlist(playlist, song).map_err(to_io_error)?;
+                }
+
+                Ok(true)
+            },
+            Command::MoveSong { playlist, from, to } => {
+                app.database().playlists().move_song(playlist, *from, *to).map_err(to_io_error)
+            },
+            Command::EditPlaylist { .. } => self.swap_playlist_details(app).map(|_| true),
+            Command::RemoveSongs { songs, memberships } => {
+                let controller = app.database().songs();
+                memberships.clear();
+
+                for song in songs.iter() {
+                    let song_memberships = controller.get_memberships(song).map_err(to_io_error)?;
+                    controller.remove_song(song.clone()).map_err(to_io_error)?;
+                    memberships.push((song.clone(), song_memberships));
+                }
+
+                Ok(true)
+            },
+            Command::TrashSongs { songs, entries, failures } => {
+                entries.clear();
+                failures.clear();
+                let mut error = None;
+
+                for song in songs.iter() {
+                    match trash::move_to_trash(app, song) {
+                        Ok(entry) => entries.push(entry),
+                        Err(e) => {
+                            println!("Failed to delete {} : {}", song.title, e);
+                            failures.push(format!("{} ({})", song.title, e));
+                            error = Some(e);
+                        }
+                    }
+                }
+
+                //the songs that were moved can still be restored, the toast tells which ones weren't
+                match error {
+                    Some(e) if entries.is_empty() => Err(e),
+                    _ => Ok(true)
+                }
+            },
+            Command::RemoveAlbum { album, tracks } => {
+                let albums = app.database().albums();
+
+                *tracks = albums.resolve_album(album);
+                albums.remove_album(album).map_err(to_io_error)?;
+
+                Ok(true)
+            }
+        }
+    }
+
+    fn revert(&mut self, app:&Arc<AppImpl>) -> std::io::Result<()> {
+        match self {
+            Command::AddToPlaylist { playlist, added, .. } => {
+                let playlists = app.database().playlists();
+
+                for song in added.iter() {
+                    playlists.remove_from_playlist(playlist, song).map_err(to_io_error)?;
+                }
+
+                Ok(())
+            },
+            Command::RemoveFromPlaylist { playlist, positions, .. } => {
+                let playlists = app.database().playlists();
+
+                for (song, position) in positions.iter() {
+                    playlists.insert_into_playlist(playlist, song, *position).map_err(to_io_error)?;
+                }
+
+                Ok(())
+            },
+            Command::MoveSong { playlist, from, to } => {
+                app.database().playlists().move_song(playlist, *to, *from).map(|_| ()).map_err(to_io_error)
+            },
+            Command::EditPlaylist { .. } => self.swap_playlist_details(app),
+            Command::RemoveSongs { memberships, .. } => {
+                let controller = app.database().songs();
+
+                for (song, song_memberships) in memberships.iter() {
+                    controller.restore_song(song, song_memberships).map_err(to_io_error)?;
+                }
+
+                Ok(())
+            },
+            Command::TrashSongs { entries, .. } => {
+                for entry in entries.iter() {
+                    trash::restore(app, entry)?;
+                }
+
+                Ok(())
+            },
+            Command::RemoveAlbum { album, tracks } => {
+                app.database().albums().add_album(album, tracks.clone()).map_err(to_io_error)
+            }
+        }
+    }
+
+    //the new details are written and the old ones kept in their place, so undoing and redoing is the same
+    fn swap_playlist_details(&mut self, app:&Arc<AppImpl>) -> std::io::Result<()> {
+        let Command::EditPlaylist { playlist, name, description } = self else {
+            return Ok(());
+        };
+
+        let playlists = app.database().playlists();
+        let current = playlists.get_playlist(&playlist.id).ok_or(to_io_error(rusqlite::Error::QueryReturnedNoRows))?;
+
+        playlists.rename_playlist(playlist, name).map_err(to_io_error)?;
+        playlists.set_description(playlist, description.clone()).map_err(to_io_error)?;
+
+        *name = current.name;
+        *description = current.description;
+
+        Ok(())
+    }
+
+    //shown in the toast after the actions that lose something
+    fn destructive_description(&self) -> Option<String> {
+        let count = |songs:&Vec<Song>| match songs.len() {
+            1 => format!("\"{}\"", songs[0].title),
+            count => format!("{} songs", count)
+        };
+
+        match self {
+            Command::RemoveFromPlaylist { playlist, songs, .. } => Some(format!("Removed {} from {}", count(songs), playlist.name)),
+            Command::RemoveSongs { songs, .. } => Some(format!("Removed {} from the library", count(songs))),
+            Command::TrashSongs { songs, failures, .. } if failures.is_empty() => Some(format!("Deleted {}", count(songs))),
+            Command::TrashSongs { songs, entries, failures } => Some(format!("Deleted {} of {} songs, could not delete {}", entries.len(), songs.len(), failures.join(", "))),
+            Command::RemoveAlbum { album, .. } => Some(format!("Removed the album {}", album.title)),
+            _ => None
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    done:Vec<Command>,
+    undone:Vec<Command>,
+    toast:Option<(String, Instant)>
+}
+
+impl UndoStack {
+    //message of the last destructive action while it is recent enough
+    pub fn toast(&self) -> Option<(String, Duration)> {
+        let (message, since) = self.toast.as_ref()?;
+        TOAST_DURATION.checked_sub(since.elapsed()).map(|remaining| (message.clone(), remaining))
+    }
+
+    pub fn dismiss_toast(&mut self) {
+        self.toast = None;
+    }
+
+    fn push(&mut self, command:Command) {
+        self.toast = command.destructive_description().map(|message| (message, Instant::now()));
+
+        self.done.push(command);
+        self.undone.clear();
+
+        if self.done.len() > MAX_COMMANDS {
+            self.done.remove(0);
+        }
+    }
+}
+
+//the stack is never locked while a command runs, commands lock the database
+pub fn execute(app:&Arc<AppImpl>, mut command:Command) -> std::io::Result<()> {
+    if command.apply(app)? {
+        app.undo_stack.lock().unwrap().push(command);
+    }
+
+    Ok(())
+}
+
+pub fn undo(app:&Arc<AppImpl>) -> std::io::Result<()> {
+    let command = {
+        let mut stack = app.undo_stack.lock().unwrap();
+        stack.dismiss_toast();
+        stack.done.pop()
+    };
+
+    if let Some(mut command) = command {
+        command.revert(app)?;
+        app.undo_stack.lock().unwrap().undone.push(command);
+    }
+
+    Ok(())
+}
+
+pub fn redo(app:&Arc<AppImpl>) -> std::io::Result<()> {
+    let command = {
+        app.undo_stack.lock().unwrap().undone.pop()
+    };
+
+    if let Some(mut command) = command {
+        command.apply(app)?;
+
+        let mut stack = app.undo_stack.lock().unwrap();
+        stack.done.push(command);
+    }
+
+    Ok(())
+}
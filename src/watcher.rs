@@ -0,0 +1,205 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::{mpsc::{self, RecvTimeoutError}, Arc, Mutex}, time::{Duration, Instant}};
+
+use notify::{event::{ModifyKind, RenameMode}, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::{app::AppImpl, library};
+
+//files are only imported once nothing has been written to them for this long
+const SETTLE_DELAY:Duration = Duration::from_secs(2);
+
+pub struct LibraryWatcher {
+    watcher:Mutex<Option<RecommendedWatcher>>
+}
+
+impl LibraryWatcher {
+    pub fn new() -> Self {
+        LibraryWatcher {
+            watcher: Mutex::new(None)
+        }
+    }
+
+    pub fn start(&self, app:Arc<AppImpl>, roots:Vec<PathBuf>) -> notify::Result<()> {
+        let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        for root in &roots {
+            if let Err(e) = watcher.watch(root, RecursiveMode::Recursive) {
+                println!("Failed to watch {} : {}", root.display(), e);
+            }
+        }
+
+        *self.watcher.lock().unwrap() = Some(watcher);
+
+        std::thread::spawn(move || {
+            let mut state = WatcherState::new(app);
+
+            loop {
+                match rx.recv_timeout(Duration::from_millis(500)) {
+                    Ok(Ok(event)) => state.handle(event),
+                    Ok(Err(e)) => println!("Watcher error : {}", e),
+                    Err(RecvTimeoutError::Timeout) => {},
+                    Err(RecvTimeoutError::Disconnected) => break
+                }
+
+                state.tick();
+            }
+        });
+
+        Ok(())
+    }
+
+    pub fn watch(&self, folder:&Path) -> notify::Result<()> {
+        if let Some(watcher) = self.watcher.lock().unwrap().as_mut() {
+            watcher.watch(folder, RecursiveMode::Recursive)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn unwatch(&self, folder:&Path) -> notify::Result<()> {
+        if let Some(watcher) = self.watcher.lock().unwrap().as_mut() {
+            watcher.unwatch(folder)?;
+        }
+
+        Ok(())
+    }
+}
+
+struct WatcherState {
+    app:Arc<AppImpl>,
+    pending_creations:HashMap<PathBuf, Instant>,
+    pending_rename:Option<(PathBuf, Instant)>
+}
+
+impl WatcherState {
+    fn new(app:Arc<AppImpl>) -> Self {
+        WatcherState {
+            app,
+            pending_creations: HashMap::new(),
+            pending_rename: None
+        }
+    }
+
+    fn handle(&mut self, event:Event) {
+        match event.kind {
+            EventKind::Create(_) => {
+                for path in event.paths {
+                    self.pending_creations.insert(path, Instant::now());
+                }
+            },
+            EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                self.flush_pending_rename();
+                self.pending_rename = event.paths.into_iter().next().map(|path| (path, Instant::now()));
+            },
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                for path in event.paths {
+                    match self.pending_rename.take() {
+                        Some((from, _)) => self.relocate(&from, &path),
+                        None => {
+                            self.pending_creations.insert(path, Instant::now());
+                        }
+                    }
+                }
+            },
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    if self.pending_rename.as_ref().map(|(path, _)| path == from).unwrap_or(false) {
+                        self.pending_rename = None;
+                    }
+
+                    self.relocate(from, to);
+                }
+            },
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                for path in event.paths {
+                    if path.exists() {
+                        self.pending_creations.insert(path, Instant::now());
+                    } else {
+                        self.remove(&path);
+                    }
+                }
+            },
+            EventKind::Modify(_) => {
+                for path in event.paths {
+                    if let Some(last_change) = self.pending_creations.get_mut(&path) {
+                        *last_change = Instant::now();
+                    }
+                }
+            },
+            EventKind::Remove(_) => {
+                for path in event.paths {
+                    self.pending_creations.remove(&path);
+                    self.remove(&path);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn tick(&mut self) {
+        //a rename without a destination means the file left the watched folders
+        if self.pending_rename.as_ref().map(|(_, time)| time.elapsed() > SETTLE_DELAY).unwrap_or(false) {
+            self.flush_pending_rename();
+        }
+
+        let settled = self.pending_creations.iter()
+            .filter(|(_, last_change)| last_change.elapsed() > SETTLE_DELAY)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+
+        for path in settled {
+            self.pending_creations.remove(&path);
+            self.import(&path);
+        }
+    }
+
+    fn flush_pending_rename(&mut self) {
+        if let Some((path, _)) = self.pending_rename.take() {
+            self.remove(&path);
+        }
+    }
+
+    fn import(&self, path:&Path) {
+        let files = if path.is_dir() {
+            library::find_audio_files(path)
+        } else {
+            vec![path.to_path_buf()]
+        };
+
+        for file in files {
+            if !file.is_file() || !library::is_audio_file(&file) {
+                continue;
+            }
+
+            //files being downloaded are added by the download manager once they are complete
+            if self.app.download_manager.is_downloading(&file) {
+                continue;
+            }
+
+            if self.app.database().songs().get_song_by_path(&file).is_some() {
+                continue;
+            }
+
+            let _ = library::import_song(&self.app, &file);
+        }
+    }
+
+    fn relocate(&self, from:&Path, to:&Path) {
+        let relocated = {
+            self.app.database().songs().relocate_path(from, to)
+        };
+
+        match relocated {
+            //moved in from a folder that isn't watched
+            Ok(0) => self.import(to),
+            Ok(_) => (),
+            Err(e) => println!("Failed to move {} to {} in the library : {}", from.display(), to.display(), e)
+        }
+    }
+
+    fn remove(&self, path:&Path) {
+        if let Err(e) = self.app.database().songs().remove_path(path) {
+            println!("Failed to remove {} from the library : {}", path.display(), e);
+        }
+    }
+}